@@ -0,0 +1,2 @@
+let d = smud::bevy::sdf(in.pos);
+return smud::default_fill::fill(d, in.color);
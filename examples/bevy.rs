@@ -27,6 +27,7 @@ impl ExtractToShader for BevyBirdShader {
     type ParamsQuery<'a> = &'a ColorParams;
     type ParamsBundle = ColorParams;
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -38,6 +39,7 @@ impl ExtractToShader for BevyBirdShader {
 
 impl ParameterizedShader for BevyBirdShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -50,6 +52,10 @@ impl ParameterizedShader for BevyBirdShader {
         Frame::square(295.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(295.0)
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [
             FragmentImport {
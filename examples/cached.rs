@@ -0,0 +1,112 @@
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+use bevy_param_shaders::prelude::*;
+
+// Draws lots of bevy birds, each one is only drawn with the expensive shader once and then copied from a texture
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .insert_resource(ClearColor(Color::srgb(0.7, 0.8, 0.7)))
+        .add_plugins((
+            DefaultPlugins,
+            ExtractToShaderPlugin::<BevyBirdShader>::default(),
+            LogDiagnosticsPlugin::filtered(vec![
+                ShaderCacheToTexture::CACHE_HITS,
+                ShaderCacheToTexture::CACHE_MISSES,
+            ]),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate)
+        .run();
+}
+
+#[repr(C)]
+#[derive(Debug, Default, TypePath)]
+pub struct BevyBirdShader;
+
+impl ExtractToShader for BevyBirdShader {
+    type Shader = BevyBirdShader;
+    type ParamsQuery<'a> = &'a ColorParams;
+    type ParamsBundle = ColorParams;
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        *query_item
+    }
+}
+
+impl ParameterizedShader for BevyBirdShader {
+    type Params = ColorParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        SDFColorCall {
+            sdf: "smud::bevy::sdf(in.pos)",
+            fill_color: "smud::default_fill::fill(d, in.color)",
+        }
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        FRAME
+    }
+
+    fn frame(_params: &Self::Params) -> Frame {
+        FRAME
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        [
+            FragmentImport {
+                path: "smud.wgsl",
+                import_path: "smud",
+            },
+            FragmentImport {
+                path: "bevy.wgsl",
+                import_path: "smud::bevy",
+            },
+            FragmentImport {
+                path: "cubic_falloff.wgsl",
+                import_path: "smud::default_fill",
+            },
+        ]
+        .into_iter()
+    }
+
+    const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
+}
+
+const FRAME: Frame = Frame::square(295.0);
+
+fn setup(mut commands: Commands) {
+    for x in -5..5 {
+        for y in -5..5 {
+            commands.spawn((
+                ShaderBundle {
+                    shape: ShaderUsage::<BevyBirdShader>::default(),
+                    parameters: Color::srgb(0.36, 0.41, 0.45).into(),
+                    transform: Transform::from_xyz(x as f32 * 60.0, y as f32 * 60.0, 0.0)
+                        .with_scale(Vec3::splat(0.1)),
+                    ..default()
+                },
+                ShaderCacheToTexture {
+                    frame: FRAME,
+                    ..default()
+                },
+            ));
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+// Rotating doesn't need the texture to be drawn again
+fn rotate(mut query: Query<&mut Transform, With<ShaderCacheToTexture>>, time: Res<Time>) {
+    for mut transform in query.iter_mut() {
+        transform.rotate_z(time.delta_seconds());
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::{color::palettes, prelude::*};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{
+    prelude::*,
+    primitives::{
+        CircleShader, PrimitivesPlugin, RectShader, RectShaderExtraction,
+        RoundedRectWithBorderShader, ShaderBorder, ShaderProportions, ShaderRounding,
+    },
+};
+
+// Rectangles, circles and bordered rectangles drawn in the same pipeline.
+// The shapes are interleaved at different z but are still drawn with a handful of draw calls
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            PrimitivesPlugin,
+            ExtractToShaderPlugin::<Rect>::default(),
+            ExtractToShaderPlugin::<Circle>::default(),
+            ExtractToShaderPlugin::<BorderedRect>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+type UiShaders = (RectShader, CircleShader, RoundedRectWithBorderShader);
+
+type Rect = CombinedExtraction<RectShaderExtraction, UiShaders>;
+type Circle = CombinedExtraction<CircleShader, UiShaders>;
+type BorderedRect = CombinedExtraction<RoundedRectWithBorderShader, UiShaders>;
+
+fn setup(mut commands: Commands) {
+    for row in 0..20 {
+        for column in 0..20 {
+            let transform = Transform::from_xyz(
+                (column as f32 - 9.5) * 40.0,
+                (row as f32 - 9.5) * 40.0,
+                ((row * 20 + column) % 7) as f32,
+            )
+            .with_scale(Vec3::splat(25.0));
+
+            match (row + column) % 3 {
+                0 => {
+                    commands.spawn(ShaderBundle::<Rect> {
+                        parameters: (
+                            palettes::css::ORANGE_RED.into(),
+                            ShaderProportions {
+                                width: 1.0,
+                                height: 0.5,
+                            },
+                        ),
+                        transform,
+                        ..default()
+                    });
+                }
+                1 => {
+                    commands.spawn(ShaderBundle::<Circle> {
+                        parameters: palettes::css::GOLD.into(),
+                        transform,
+                        ..default()
+                    });
+                }
+                _ => {
+                    commands.spawn(ShaderBundle::<BorderedRect> {
+                        parameters: (
+                            palettes::css::DODGER_BLUE.into(),
+                            ShaderRounding { rounding: 0.2 },
+                            ShaderProportions::default(),
+                            ShaderBorder::from_color(Color::WHITE),
+                        ),
+                        transform,
+                        ..default()
+                    });
+                }
+            }
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
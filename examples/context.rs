@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{prelude::*, primitives::CircleShader};
+
+// Circles colored by where they are in the world.
+// The color comes from the shape's `GlobalTransform`, so it changes as the circles move without any per-entity color component
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            bevy_param_shaders::primitives::PrimitivesPlugin,
+            ExtractToShaderPlugin::<WorldColoredCircle>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate)
+        .run();
+}
+
+pub struct WorldColoredCircle;
+
+impl ExtractToShader for WorldColoredCircle {
+    type Shader = CircleShader;
+    type ParamsQuery<'a> = ();
+    type ParamsBundle = ();
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        // shapes are always extracted with their context, so this is not used
+        Color::WHITE.into()
+    }
+
+    fn get_params_with_context(
+        _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+        context: &ExtractionContext,
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        let position = context.transform.translation();
+        let hue = (position.x.atan2(position.y).to_degrees() + 360.0) % 360.0;
+        let lightness = 0.3 + (position.length() / 600.0).min(0.5);
+
+        Color::hsl(hue, 0.8, lightness).into()
+    }
+}
+
+#[derive(Debug, Component)]
+struct Orbit;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn((SpatialBundle::default(), Orbit))
+        .with_children(|builder| {
+            for ring in 1..6 {
+                let count = ring * 8;
+                for index in 0..count {
+                    let angle = index as f32 / count as f32 * std::f32::consts::TAU;
+                    let radius = ring as f32 * 50.0;
+
+                    builder.spawn(ShaderBundle::<WorldColoredCircle> {
+                        parameters: (),
+                        transform: Transform::from_xyz(
+                            angle.cos() * radius,
+                            angle.sin() * radius,
+                            0.0,
+                        )
+                        .with_scale(Vec3::splat(10.0)),
+                        ..default()
+                    });
+                }
+            }
+        });
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn rotate(mut orbits: Query<&mut Transform, With<Orbit>>, time: Res<Time>) {
+    for mut transform in orbits.iter_mut() {
+        transform.translation.x = (time.elapsed_seconds() * 0.5).sin() * 150.0;
+        transform.rotate_z(time.delta_seconds() * 0.3);
+    }
+}
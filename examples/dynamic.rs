@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use bevy_param_shaders::prelude::*;
+
+// Shapes drawn with a shader defined in `assets/ring.pshader.ron` rather than in Rust
+// Hot reload works! Try modifying the fragment body in the file
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((DefaultPlugins, DynamicShaderPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let ring: Handle<DynamicShaderDefinition> = asset_server.load("ring.pshader.ron");
+
+    for index in 0..5 {
+        let hue = index as f32 * 72.0;
+        let color: LinearRgba = Color::hsl(hue, 0.8, 0.6).into();
+
+        commands.spawn(DynamicShaderBundle {
+            // params are matched to those declared in the file by name
+            params: DynamicShaderParams::new(ring.clone())
+                .with("color", color)
+                .with("thickness", 0.02 + index as f32 * 0.02),
+            transform: Transform::from_xyz((index as f32 - 2.0) * 220.0, 0.0, 0.0)
+                .with_scale(Vec3::splat(100.0)),
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
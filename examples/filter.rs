@@ -0,0 +1,98 @@
+use bevy::{color::palettes, prelude::*};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{
+    prelude::*,
+    primitives::{
+        CircleShader, PrimitivesPlugin, RoundedRectWithBorderShader,
+        RoundedRectWithBorderShaderParams,
+    },
+};
+
+// Circles with an outline drawn around the selected ones.
+// The outline is only extracted for entities `With<Selected>`, so selecting a circle is just a matter of adding the marker
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            PrimitivesPlugin,
+            ExtractToShaderPlugin::<SelectionOutline>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, change_selection)
+        .run();
+}
+
+#[derive(Debug, Component)]
+pub struct Selected;
+
+#[derive(Debug, Component)]
+pub struct Index(usize);
+
+pub struct SelectionOutline;
+
+impl ExtractToShader for SelectionOutline {
+    type Shader = RoundedRectWithBorderShader;
+    type ParamsQuery<'a> = ();
+    type ParamsBundle = ();
+    type ResourceParams<'a> = ();
+    type Filter = With<Selected>;
+
+    fn get_params(
+        _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        RoundedRectWithBorderShaderParams {
+            width: 1.3,
+            height: 1.3,
+            rounding: 1.0,
+            color: LinearRgba::NONE,
+            border_color: LinearRgba::WHITE,
+            border: 0.05,
+        }
+    }
+}
+
+const COUNT: usize = 10;
+
+fn setup(mut commands: Commands) {
+    for row in 0..COUNT {
+        for column in 0..COUNT {
+            commands.spawn((
+                ShaderBundle::<CircleShader> {
+                    parameters: palettes::css::DODGER_BLUE.into(),
+                    transform: Transform::from_xyz(
+                        (column as f32 - 4.5) * 60.0,
+                        (row as f32 - 4.5) * 60.0,
+                        0.0,
+                    )
+                    .with_scale(Vec3::splat(20.0)),
+                    ..default()
+                },
+                ShaderUsage::<SelectionOutline>::default(),
+                Index(row * COUNT + column),
+            ));
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn change_selection(
+    mut commands: Commands,
+    circles: Query<(Entity, &Index, Has<Selected>)>,
+    time: Res<Time>,
+) {
+    let step = time.elapsed_seconds() as usize;
+
+    for (entity, index, selected) in circles.iter() {
+        let should_select = (index.0 + step) % 7 == 3;
+        if should_select && !selected {
+            commands.entity(entity).insert(Selected);
+        } else if !should_select && selected {
+            commands.entity(entity).remove::<Selected>();
+        }
+    }
+}
@@ -37,6 +37,7 @@ macro_rules! define_sdf_shader {
             type ParamsQuery<'a> = &'a ColorParams;
             type ParamsBundle = ColorParams;
             type ResourceParams<'a> = ();
+            type Filter = ();
 
             fn get_params(
                 query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -48,6 +49,7 @@ macro_rules! define_sdf_shader {
 
         impl ParameterizedShader for $name {
             type Params = ColorParams;
+            type Uniforms = NoUniforms;
 
             fn fragment_body() -> impl Into<String> {
                 SDFAlphaCall {
@@ -69,6 +71,10 @@ macro_rules! define_sdf_shader {
                 Frame::square(1.)
             }
 
+            fn frame(_params: &Self::Params) -> Frame {
+                Frame::square(1.)
+            }
+
             const UUID: u128 = $uuid;
         }
     };
@@ -0,0 +1,62 @@
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{
+    prelude::*,
+    primitives::{CircleShader, PrimitivesPlugin},
+};
+
+// A large grid of circles, most of which are off screen.
+// The circles are culled by a compute shader rather than on the CPU
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .insert_resource(ShapeCulling::Gpu)
+        .add_plugins((
+            DefaultPlugins,
+            LogDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
+            PrimitivesPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_camera)
+        .run();
+}
+
+const SIZE: i32 = 300;
+const SPACING: f32 = 30.0;
+
+fn setup(mut commands: Commands) {
+    for row in 0..SIZE {
+        for column in 0..SIZE {
+            let color = Color::hsl((row + column) as f32 * 3.0 % 360.0, 0.8, 0.6);
+
+            commands.spawn(ShaderBundle::<CircleShader> {
+                parameters: color.into(),
+                transform: Transform::from_xyz(
+                    (column - SIZE / 2) as f32 * SPACING,
+                    (row - SIZE / 2) as f32 * SPACING,
+                    0.0,
+                )
+                .with_scale(Vec3::splat(10.0)),
+                ..default()
+            });
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn move_camera(mut cameras: Query<&mut Transform, With<Camera>>, time: Res<Time>) {
+    let t = time.elapsed_seconds() * 0.2;
+    let radius = SIZE as f32 * SPACING * 0.3;
+
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = t.cos() * radius;
+        transform.translation.y = t.sin() * radius;
+    }
+}
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use bevy_param_shaders::prelude::*;
-//Hot reload works! Try modifying bevy.wgsl
+//Hot reload works! Try modifying bevy.wgsl, or the fragment body in bevy_bird.body.wgsl
 
 
 fn main() {
@@ -24,6 +24,7 @@ impl ExtractToShader for BevyBirdShader {
     type ParamsQuery<'a> = &'a ColorParams;
     type ParamsBundle = ColorParams;
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -35,6 +36,7 @@ impl ExtractToShader for BevyBirdShader {
 
 impl ParameterizedShader for BevyBirdShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -47,6 +49,10 @@ impl ParameterizedShader for BevyBirdShader {
         Frame::square(295.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(295.0)
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [
             FragmentImport {
@@ -65,6 +71,9 @@ impl ParameterizedShader for BevyBirdShader {
         .into_iter()
     }
 
+    // replaces `fragment_body` once it has loaded
+    const FRAGMENT_BODY_PATH: Option<&'static str> = Some("bevy_bird.body.wgsl");
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{prelude::*, primitives::CircleShader};
+
+// Shapes drawn each frame without spawning any entities
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            bevy_param_shaders::primitives::PrimitivesPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (draw_spiral, draw_cursor))
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn draw_spiral(mut shapes: ParamShapes<CircleShader>, time: Res<Time>) {
+    let t = time.elapsed_seconds();
+    for index in 0..100 {
+        let turn = index as f32 * 0.2 + t;
+        let position = Vec2::from_angle(turn) * (index as f32 * 3.0);
+        let hue = (index as f32 * 3.6 + t * 30.0) % 360.0;
+
+        shapes.draw(
+            Transform::from_translation(position.extend(0.0)).with_scale(Vec3::splat(8.0)),
+            Color::hsl(hue, 0.8, 0.5).into(),
+        );
+    }
+}
+
+fn draw_cursor(
+    mut shapes: ParamShapes<CircleShader>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Some(cursor) = windows.iter().find_map(Window::cursor_position) else {
+        return;
+    };
+    let Some(position) = cameras
+        .iter()
+        .find_map(|(camera, transform)| camera.viewport_to_world_2d(transform, cursor))
+    else {
+        return;
+    };
+
+    shapes.draw(
+        Transform::from_translation(position.extend(1.0)).with_scale(Vec3::splat(20.0)),
+        Color::WHITE.with_alpha(0.8).into(),
+    );
+}
@@ -25,6 +25,7 @@ impl ExtractToShader for BlueSquare {
     type ParamsQuery<'a> = ();
     type ParamsBundle = ();
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -43,6 +44,7 @@ impl ExtractToShader for RedSquare {
     type ParamsQuery<'a> = ();
     type ParamsBundle = ();
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -62,6 +64,7 @@ pub struct RectangleShader;
 
 impl ParameterizedShader for RectangleShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         "return in.color;"
@@ -78,6 +81,13 @@ impl ParameterizedShader for RectangleShader {
         }
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame {
+            half_width: 100.0,
+            half_height: 50.0,
+        }
+    }
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
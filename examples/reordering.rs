@@ -0,0 +1,52 @@
+use bevy::{color::palettes, prelude::*};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{
+    prelude::*,
+    primitives::{CircleShader, PrimitivesPlugin, RectShaderExtraction, ShaderProportions},
+};
+
+// Rectangles and circles at alternating z.
+// None of the shapes overlap, so they can be reordered and drawn with one batch per shader
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .insert_resource(ShapeBatchReordering::NonOverlapping)
+        .add_plugins((DefaultPlugins, PrimitivesPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    for row in 0..20 {
+        for column in 0..20 {
+            let index = row * 20 + column;
+            let transform = Transform::from_xyz(
+                (column as f32 - 9.5) * 40.0,
+                (row as f32 - 9.5) * 40.0,
+                index as f32 * 0.01,
+            )
+            .with_scale(Vec3::splat(15.0));
+
+            if index % 2 == 0 {
+                commands.spawn(ShaderBundle::<RectShaderExtraction> {
+                    parameters: (
+                        palettes::css::ORANGE_RED.into(),
+                        ShaderProportions::default(),
+                    ),
+                    transform,
+                    ..default()
+                });
+            } else {
+                commands.spawn(ShaderBundle::<CircleShader> {
+                    parameters: palettes::css::GOLD.into(),
+                    transform,
+                    ..default()
+                });
+            }
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
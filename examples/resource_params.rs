@@ -21,16 +21,34 @@ fn main() {
     app.run();
 }
 
-#[repr(C)]
-#[derive(
-    Debug, Clone, Copy, PartialEq, Default, Reflect, bytemuck::Pod, bytemuck::Zeroable, Component,
-)]
-pub struct MorphParams {
-    pub color: LinearRgba,
-    pub time: f32,
+pub use morph_uniforms::MorphUniforms;
+
+mod morph_uniforms {
+    // the `ShaderType` derive generates a function for each field which newer compilers report as unused
+    #![allow(dead_code)]
+
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// The color and time are the same for every shape, so they are set once per frame rather than copied into every instance
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, ShaderType)]
+    pub struct MorphUniforms {
+        pub color: LinearRgba,
+        pub time: f32,
+    }
 }
 
-impl ShaderParams for MorphParams {}
+impl ShaderUniforms for MorphUniforms {
+    type ResourceParams<'a> = (Res<'a, Time>, Res<'a, ColorResource>);
+
+    fn get_uniforms(
+        resources: &<Self::ResourceParams<'_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+    ) -> Self {
+        MorphUniforms {
+            color: resources.1.color,
+            time: resources.0.elapsed_seconds_wrapped(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Resource)]
 pub struct ColorResource {
@@ -57,26 +75,25 @@ impl ExtractToShader for BevyMorphShader {
     type Shader = Self;
     type ParamsQuery<'a> = ();
     type ParamsBundle = ();
-    type ResourceParams<'a> = (Res<'a, Time>, Res<'a, ColorResource>);
+    type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
-        resources: &<Self::ResourceParams<'_> as bevy::ecs::system::SystemParam>::Item<'_, '_>,
+        _resources: &(),
     ) -> <Self::Shader as ParameterizedShader>::Params {
-        MorphParams {
-            color: resources.1.color,
-            time: resources.0.elapsed_seconds_wrapped(),
-        }
+        NoParams
     }
 }
 
 impl ParameterizedShader for BevyMorphShader {
-    type Params = MorphParams;
+    type Params = NoParams;
+    type Uniforms = MorphUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall{
-            sdf:"mix(smud::sd_circle(in.pos, 250.0), smud::bevy::sdf(in.pos), sin(in.time) * 0.5 + 0.5)",
-            fill_color: "smud::default_fill::fill(d, in.color)"
+            sdf:"mix(smud::sd_circle(in.pos, 250.0), smud::bevy::sdf(in.pos), sin(uniforms.time) * 0.5 + 0.5)",
+            fill_color: "smud::default_fill::fill(d, uniforms.color)"
         }
     }
 
@@ -102,6 +119,10 @@ impl ParameterizedShader for BevyMorphShader {
         Frame::square(295.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(295.0)
+    }
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
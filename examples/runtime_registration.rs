@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::prelude::*;
+
+// A shader registered while the app is running, instead of with a plugin.
+// Press space to unregister and register it again
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((DefaultPlugins, ParamShaderRegistryPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, toggle)
+        .run();
+}
+
+#[derive(Debug, TypePath, Default)]
+pub struct DiamondShader;
+
+impl ExtractToShader for DiamondShader {
+    type Shader = Self;
+    type ParamsQuery<'a> = &'a ColorParams;
+    type ParamsBundle = ColorParams;
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        *query_item
+    }
+}
+
+impl ParameterizedShader for DiamondShader {
+    type Params = ColorParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        r#"let d = abs(in.pos.x) + abs(in.pos.y) - 1.0;
+        let aaf = 0.71 * fwidth(d);
+        return vec4<f32>(in.color.rgb, in.color.a * smoothstep(aaf, -aaf, d));"#
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        [].into_iter()
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        Frame::square(1.0)
+    }
+
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(1.0)
+    }
+
+    const UUID: u128 = 0x5c2d7e9f1a3b4c6d8e0f1a2b3c4d5e6f;
+}
+
+fn setup(mut commands: Commands) {
+    commands.register_param_shader::<DiamondShader>();
+
+    for index in 0..5 {
+        commands.spawn(ShaderBundle::<DiamondShader> {
+            parameters: Color::hsl(index as f32 * 72.0, 0.8, 0.5).into(),
+            transform: Transform::from_xyz((index as f32 - 2.0) * 120.0, 0.0, 0.0)
+                .with_scale(Vec3::splat(50.0)),
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn toggle(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    registry: Res<ParamShaderRegistry>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        if registry.is_registered::<DiamondShader>() {
+            commands.unregister_param_shader::<DiamondShader>();
+        } else {
+            commands.register_param_shader::<DiamondShader>();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::prelude::*;
+
+// Line segments described by their end points.
+// Each segment's quad is derived from its end points when it is extracted, so the entities themselves never move
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            ExtractToShaderPlugin::<SegmentShader>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, wave)
+        .run();
+}
+
+/// A line between two points in the entity's local space
+#[derive(Debug, Clone, Copy, PartialEq, Component, Default)]
+pub struct Segment {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub width: f32,
+    pub color: Color,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SegmentParams {
+    pub color: LinearRgba,
+    pub start: Vec2,
+    pub end: Vec2,
+    pub width: f32,
+}
+
+impl ShaderParams for SegmentParams {}
+
+#[derive(Debug, TypePath, Default)]
+pub struct SegmentShader;
+
+impl ExtractToShader for SegmentShader {
+    type Shader = Self;
+    type ParamsQuery<'a> = &'a Segment;
+    type ParamsBundle = Segment;
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        SegmentParams {
+            color: query_item.color.into(),
+            start: query_item.start,
+            end: query_item.end,
+            width: query_item.width,
+        }
+    }
+
+    fn instance_transform(params: &SegmentParams, context: &ExtractionContext) -> GlobalTransform {
+        // the quad is centered between the points and covers the rounded ends
+        let direction = params.end - params.start;
+        let local = Transform {
+            translation: ((params.start + params.end) * 0.5).extend(0.0),
+            rotation: Quat::from_rotation_z(direction.to_angle()),
+            scale: Vec3::splat((direction.length() + params.width) * 0.5),
+        };
+
+        context.transform.mul_transform(local)
+    }
+}
+
+impl ParameterizedShader for SegmentShader {
+    type Params = SegmentParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        r#"let total = length(in.end - in.start) + in.width;
+        let half_length = length(in.end - in.start) / total;
+        let half_width = in.width / total;
+        let d = length(vec2<f32>(max(abs(in.pos.x) - half_length, 0.0), in.pos.y)) - half_width;
+        let aaf = 0.71 * fwidth(d);
+        return vec4<f32>(in.color.rgb, in.color.a * smoothstep(aaf, -aaf, d));"#
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        [].into_iter()
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        "vec2<f32>(1.0, vertex.width / (length(vertex.end - vertex.start) + vertex.width))"
+    }
+
+    fn frame(params: &Self::Params) -> Frame {
+        Frame {
+            half_width: 1.0,
+            half_height: params.width / (params.start.distance(params.end) + params.width),
+        }
+    }
+
+    const UUID: u128 = 0x8b1e2f4a6c3d4e5f9a0b1c2d3e4f5a6b;
+}
+
+const COUNT: usize = 24;
+
+fn setup(mut commands: Commands) {
+    for index in 0..COUNT {
+        let hue = index as f32 / COUNT as f32 * 360.0;
+        commands.spawn(ShaderBundle::<SegmentShader> {
+            parameters: Segment {
+                start: Vec2::ZERO,
+                end: Vec2::X,
+                width: 12.0,
+                color: Color::hsl(hue, 0.8, 0.5),
+            },
+            ..default()
+        });
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn wave(mut segments: Query<&mut Segment>, time: Res<Time>) {
+    let t = time.elapsed_seconds();
+    for (index, mut segment) in segments.iter_mut().enumerate() {
+        let angle = index as f32 / COUNT as f32 * std::f32::consts::TAU;
+        let direction = Vec2::from_angle(angle);
+        let length = 150.0 + 100.0 * (t * 2.0 + angle * 3.0).sin();
+
+        segment.start = direction * 40.0;
+        segment.end = direction * length;
+    }
+}
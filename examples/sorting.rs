@@ -29,6 +29,7 @@ macro_rules! define_sdf_shader {
             type ParamsQuery<'a> = &'a ColorParams;
             type ParamsBundle = ColorParams;
             type ResourceParams<'a> = ();
+            type Filter = ();
 
             fn get_params(
                 query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -40,6 +41,7 @@ macro_rules! define_sdf_shader {
 
         impl ParameterizedShader for $name {
             type Params = ColorParams;
+            type Uniforms = NoUniforms;
 
             fn fragment_body() -> impl Into<String> {
                 SDFAlphaCall {
@@ -61,6 +63,10 @@ macro_rules! define_sdf_shader {
                 Frame::square(1.0)
             }
 
+            fn frame(_params: &Self::Params) -> Frame {
+                Frame::square(1.0)
+            }
+
             const UUID: u128 = $uuid;
         }
     };
@@ -139,6 +145,31 @@ fn setup(mut commands: Commands) {
         spawn_bundle!(HeartShader, 3.0, heart_color);
     }
 
+    // These all have the same transform z, so they are ordered by their `ShaderZIndex` instead
+    for i in 0..w {
+        macro_rules! spawn_bundle {
+            ($name:ident, $order:literal, $color:ident) => {
+                commands.spawn((
+                    ShaderBundle::<$name> {
+                        transform: Transform::from_translation(Vec3::new(
+                            i as f32 * spacing - w as f32 * spacing / 2.,
+                            -300.0,
+                            0.0,
+                        ))
+                        .with_scale(Vec3::ONE * spacing * 0.75),
+                        parameters: ($color).into(),
+                        ..default()
+                    },
+                    ShaderZIndex::new(0, $order),
+                ))
+            };
+        }
+
+        spawn_bundle!(BoxShader, 3.0, box_color);
+        spawn_bundle!(CircleShader, 2.0, circle_color);
+        spawn_bundle!(HeartShader, 1.0, heart_color);
+    }
+
     commands.spawn((Camera2dBundle::default(), PanCam::default()));
     commands.spawn(Camera2dBundle::default());
 }
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{prelude::*, primitives::CircleShader};
+use rand::prelude::*;
+
+// Thousands of stars drawn from a single entity.
+// Each star is an instance with its own offset and color, the whole field turns with its entity's transform
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((
+            DefaultPlugins,
+            bevy_param_shaders::primitives::PrimitivesPlugin,
+            MultiShapeInstancesPlugin::<CircleShader>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (rotate, twinkle))
+        .run();
+}
+
+const STARS: usize = 5000;
+
+fn setup(mut commands: Commands) {
+    let mut rng = rand::thread_rng();
+
+    let instances = (0..STARS)
+        .map(|_| {
+            let position = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU))
+                * rng.gen_range(0.0f32..1.0).sqrt()
+                * 800.0;
+            let size = rng.gen_range(1.0..4.0);
+            ShapeInstance::new(
+                Transform::from_translation(position.extend(0.0)).with_scale(Vec3::splat(size)),
+                ColorParams::from(Color::WHITE),
+            )
+        })
+        .collect();
+
+    commands.spawn(MultiShapeBundle::<CircleShader> {
+        instances,
+        ..default()
+    });
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn rotate(
+    mut fields: Query<&mut Transform, With<MultiShapeInstances<CircleShader>>>,
+    time: Res<Time>,
+) {
+    for mut transform in fields.iter_mut() {
+        transform.rotate_z(time.delta_seconds() * 0.05);
+    }
+}
+
+fn twinkle(mut fields: Query<&mut MultiShapeInstances<CircleShader>>, time: Res<Time>) {
+    let t = time.elapsed_seconds();
+    for mut field in fields.iter_mut() {
+        for (index, star) in field.instances.iter_mut().enumerate() {
+            let brightness = 0.6 + 0.4 * (t * 3.0 + index as f32 * 1.7).sin();
+            star.params = Color::srgb(brightness, brightness, brightness * 0.9).into();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::{
+    prelude::*,
+    primitives::{PrimitivesPlugin, RoundedRectWithBorderShader},
+};
+
+// Many shapes sharing one style.
+// Edit `assets/enemy.style.ron` while this is running to restyle every shape at once,
+// or press space to change the style from code
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            PrimitivesPlugin,
+            ShapeStylePlugin::<RoundedRectWithBorderShader>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, change_style)
+        .run();
+}
+
+#[derive(Resource)]
+struct EnemyStyle(Handle<ShapeStyle<RoundedRectWithBorderShader>>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = asset_server.load("enemy.style.ron");
+
+    for row in 0..20 {
+        for column in 0..20 {
+            commands.spawn(ShaderBundle::<StyledShape<RoundedRectWithBorderShader>> {
+                parameters: style.clone(),
+                transform: Transform::from_xyz(
+                    (column as f32 - 9.5) * 40.0,
+                    (row as f32 - 9.5) * 40.0,
+                    0.0,
+                )
+                .with_scale(Vec3::splat(15.0)),
+                ..default()
+            });
+        }
+    }
+
+    commands.insert_resource(EnemyStyle(style));
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn change_style(
+    keys: Res<ButtonInput<KeyCode>>,
+    enemy_style: Res<EnemyStyle>,
+    mut styles: ResMut<Assets<ShapeStyle<RoundedRectWithBorderShader>>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+    if let Some(style) = styles.get_mut(&enemy_style.0) {
+        style.params.rounding = 0.5 - style.params.rounding;
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::prelude::*;
+
+// Circles colored by looking up their distance from the center in a palette image.
+// Each circle uses one of two palettes, the circles using each palette are drawn together
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .add_plugins((
+            DefaultPlugins,
+            ExtractToShaderPlugin::<PaletteShader>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+#[derive(Debug, TypePath, Default)]
+pub struct PaletteShader;
+
+impl ExtractToShader for PaletteShader {
+    type Shader = Self;
+    type ParamsQuery<'a> = ();
+    type ParamsBundle = ();
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        _query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _resource: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        NoParams
+    }
+}
+
+impl ParameterizedShader for PaletteShader {
+    type Params = NoParams;
+    type Uniforms = NoUniforms;
+
+    const USE_TEXTURE: bool = true;
+
+    fn fragment_body() -> impl Into<String> {
+        r#"let d = length(in.pos);
+        let c = textureSample(shape_texture, shape_sampler, vec2<f32>(d, 0.5));
+        return vec4<f32>(c.rgb, c.a * (1.0 - smoothstep(0.98, 1.0, d)));"#
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        [].into_iter()
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        Frame::square(1.0)
+    }
+
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(1.0)
+    }
+
+    const UUID: u128 = 0x3f0c9a6e1d2b4c78a5e6f1b2c3d4e5f6;
+}
+
+/// A horizontal gradient through the given colors
+fn palette(colors: &[Srgba]) -> Image {
+    const WIDTH: usize = 64;
+    let data = (0..WIDTH)
+        .flat_map(|x| {
+            let t = x as f32 / (WIDTH - 1) as f32 * (colors.len() - 1) as f32;
+            let index = (t as usize).min(colors.len() - 2);
+            let color = colors[index].mix(&colors[index + 1], t - index as f32);
+            color.to_u8_array()
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: WIDTH as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    use bevy::color::palettes::css;
+
+    let palettes = [
+        images.add(palette(&[
+            css::WHITE,
+            css::GOLD,
+            css::ORANGE_RED,
+            css::DARK_RED,
+        ])),
+        images.add(palette(&[
+            css::WHITE,
+            css::AQUA,
+            css::DODGER_BLUE,
+            css::NAVY,
+        ])),
+    ];
+
+    for row in 0..10 {
+        for column in 0..10 {
+            commands.spawn((
+                ShaderBundle::<PaletteShader> {
+                    parameters: (),
+                    transform: Transform::from_xyz(
+                        (column as f32 - 4.5) * 60.0,
+                        (row as f32 - 4.5) * 60.0,
+                        0.0,
+                    )
+                    .with_scale(Vec3::splat(25.0)),
+                    ..default()
+                },
+                palettes[(row + column) % 2].clone(),
+            ));
+        }
+    }
+
+    commands.spawn(Camera2dBundle::default());
+}
@@ -24,6 +24,7 @@ impl ExtractToShader for BevyMorphShader {
     type ParamsQuery<'a> = &'a ColorParams;
     type ParamsBundle = ColorParams;
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -35,6 +36,7 @@ impl ExtractToShader for BevyMorphShader {
 
 impl ParameterizedShader for BevyMorphShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     const USE_TIME: bool = true;
 
@@ -67,6 +69,10 @@ impl ParameterizedShader for BevyMorphShader {
         Frame::square(295.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(295.0)
+    }
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
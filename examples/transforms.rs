@@ -30,6 +30,7 @@ impl ExtractToShader for BevyBirdShader {
     type ParamsQuery<'a> = &'a ColorParams;
     type ParamsBundle = ColorParams;
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -41,6 +42,7 @@ impl ExtractToShader for BevyBirdShader {
 
 impl ParameterizedShader for BevyBirdShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -71,6 +73,10 @@ impl ParameterizedShader for BevyBirdShader {
         Frame::square(295.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(295.0)
+    }
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
@@ -1,8 +1,9 @@
-use bevy::{color::palettes, prelude::*};
+use bevy::{color::palettes, prelude::*, render::view::RenderLayers};
 // The prelude contains the basic things needed to create shapes
 use bevy_param_shaders::prelude::*;
 
 // Should show the same circle twice with different bloom settings.
+// The blue circle is on a different render layer so only the second camera shows it
 // Currently does not work
 fn main() {
     App::new()
@@ -23,6 +24,7 @@ impl ExtractToShader for CircleShader {
     type ParamsQuery<'a> = &'a ColorParams;
     type ParamsBundle = ColorParams;
     type ResourceParams<'a> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -38,6 +40,7 @@ pub struct CircleShader;
 
 impl ParameterizedShader for CircleShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFAlphaCall {
@@ -59,6 +62,10 @@ impl ParameterizedShader for CircleShader {
         Frame::square(1.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(1.0)
+    }
+
     const UUID: u128 = 0x6d31023450194cd49f60ebabd7dca30b;
 }
 
@@ -74,6 +81,21 @@ fn setup(mut commands: Commands) {
         ..default()
     });
 
+    commands.spawn((
+        ShaderBundle {
+            shape: ShaderUsage::<CircleShader>::default(),
+
+            parameters: ColorParams {
+                color: palettes::css::BLUE.into(),
+            },
+
+            transform: Transform::from_scale(Vec3::ONE * 50.0)
+                .with_translation(Vec3::new(0.0, 200.0, 0.0)),
+            ..default()
+        },
+        RenderLayers::layer(1),
+    ));
+
     commands.spawn((
         Camera2dBundle {
             camera: Camera {
@@ -105,5 +127,6 @@ fn setup(mut commands: Commands) {
             intensity: 0.4,
             ..default()
         },
+        RenderLayers::from_layers(&[0, 1]),
     ));
 }
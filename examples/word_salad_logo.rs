@@ -253,6 +253,7 @@ impl ExtractToShader for WordLineSegmentShader {
     );
     type ParamsBundle = (WordLineDirection, ShaderColor, ShaderSecondColor);
     type ResourceParams<'w> = Res<'w, WordLineGlobalValues>;
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -296,6 +297,7 @@ struct WordLineSegmentShader;
 
 impl ParameterizedShader for WordLineSegmentShader {
     type Params = WordLineSegmentShaderParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -322,6 +324,10 @@ impl ParameterizedShader for WordLineSegmentShader {
         Frame::square(2.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(2.0)
+    }
+
     const UUID: u128 = 0xa68d391613854269a5124561eccd664d;
 }
 
@@ -0,0 +1,121 @@
+use bevy::{color::palettes, prelude::*};
+// The prelude contains the basic things needed to create shapes
+use bevy_param_shaders::prelude::*;
+
+// Circles lower on the screen are drawn in front, as in a top-down game.
+// The white circle moves up and down through the others.
+fn main() {
+    App::new()
+        // bevy_smud comes with anti-aliasing built into the standards fills
+        // which is more efficient than MSAA, and also works on Linux, wayland
+        .insert_resource(Msaa::Off)
+        .insert_resource(ClearColor(Color::BLACK))
+        .insert_resource(ParamShaderSortMode::Y)
+        .add_plugins((
+            DefaultPlugins,
+            ExtractToShaderPlugin::<CircleShader>::default(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_circle)
+        .run();
+}
+
+impl ExtractToShader for CircleShader {
+    type Shader = Self;
+    type ParamsQuery<'a> = &'a ColorParams;
+    type ParamsBundle = ColorParams;
+    type ResourceParams<'a> = ();
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
+        _r: &(),
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        *query_item
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, TypePath, Default)]
+pub struct CircleShader;
+
+impl ParameterizedShader for CircleShader {
+    type Params = ColorParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        SDFAlphaCall {
+            sdf: "smud::sd_circle(in.pos, 1.0)",
+            fill_alpha: "smud::sd_fill_alpha_fwidth(d)",
+            color: "in.color",
+        }
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        [FragmentImport {
+            path: "smud.wgsl",
+            import_path: "smud",
+        }]
+        .into_iter()
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        Frame::square(1.0)
+    }
+
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(1.0)
+    }
+
+    const UUID: u128 = 0x0c3e8a7b3d2f4e51a9b6f1d2e3c4a5b6;
+}
+
+#[derive(Debug, Component)]
+struct Moving;
+
+fn setup(mut commands: Commands) {
+    let colors = [
+        palettes::css::ORANGE_RED,
+        palettes::css::GOLD,
+        palettes::css::LIME,
+        palettes::css::DODGER_BLUE,
+    ];
+
+    for row in 0..6 {
+        let y = 150.0 - row as f32 * 60.0;
+        for column in 0..4 {
+            let x = column as f32 * 150.0 - 300.0;
+
+            commands.spawn(ShaderBundle {
+                shape: ShaderUsage::<CircleShader>::default(),
+                parameters: ColorParams {
+                    color: colors[(row + column) % colors.len()].into(),
+                },
+                transform: Transform::from_xyz(x, y, 0.0).with_scale(Vec3::splat(50.0)),
+                ..default()
+            });
+        }
+    }
+
+    commands.spawn((
+        ShaderBundle {
+            shape: ShaderUsage::<CircleShader>::default(),
+            parameters: ColorParams {
+                color: palettes::css::WHITE.into(),
+            },
+            transform: Transform::from_xyz(-225.0, 0.0, 0.0).with_scale(Vec3::splat(40.0)),
+            ..default()
+        },
+        // sort by the bottom of the circle
+        ShaderYSortOffset(-40.0),
+        Moving,
+    ));
+
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn move_circle(mut query: Query<&mut Transform, With<Moving>>, time: Res<Time>) {
+    for mut transform in query.iter_mut() {
+        transform.translation.y = time.elapsed_seconds().sin() * 200.0;
+    }
+}
@@ -0,0 +1,214 @@
+use bevy::{
+    ecs::{
+        entity::EntityHashSet,
+        system::{StaticSystemParam, SystemChangeTick},
+        world::EntityRef,
+    },
+    math::Vec3A,
+    prelude::*,
+    render::primitives::Aabb,
+};
+
+use crate::{
+    cache::ShaderCacheToTexture,
+    frame::Frame,
+    param_changes::ParamComponents,
+    parameterized_shader::{ExtractToShader, ExtractionContext, ParameterizedShader},
+    ShaderUsage, ShapeCulling,
+};
+
+/// Keeps the bounds of shapes up to date with their frames so they can be frustum culled.
+///
+/// Bounds are only recalculated for shapes whose params or transform have changed.
+/// Shapes culled on the GPU have no bounds, so that every shape is drawn.
+pub(crate) fn update_shape_aabbs<'w, Extractable: ExtractToShader>(
+    mut commands: Commands,
+    culling: Res<ShapeCulling>,
+    param_components: Res<ParamComponents<Extractable>>,
+    ticks: SystemChangeTick,
+    mut shapes: ParamSet<(
+        Query<
+            (
+                Entity,
+                EntityRef,
+                Extractable::ParamsQuery<'static>,
+                &GlobalTransform,
+                Option<&ShaderCacheToTexture>,
+                Has<Aabb>,
+            ),
+            (With<ShaderUsage<Extractable>>, Extractable::Filter),
+        >,
+        Query<&mut Aabb>,
+    )>,
+    mut removed_caches: RemovedComponents<ShaderCacheToTexture>,
+    resource_params: StaticSystemParam<Extractable::ResourceParams<'w>>,
+    mut changed: Local<Vec<(Entity, Option<Aabb>)>>,
+) {
+    let removed_caches: EntityHashSet = removed_caches.read().collect();
+
+    for (entity, entity_ref, params_item, transform, cache, has_aabb) in shapes.p0().iter() {
+        let needs_aabb = cache.is_some() || *culling != ShapeCulling::Gpu;
+        if !culling.is_changed()
+            && has_aabb == needs_aabb
+            && !removed_caches.contains(&entity)
+            && !param_components.changed(&entity_ref, &ticks)
+        {
+            continue;
+        }
+
+        // cached shapes are drawn using the cache's frame
+        let new_aabb = match cache {
+            Some(cache) => Some(instance_aabb(cache.frame, transform, transform)),
+            None if !needs_aabb => None,
+            None => {
+                let context = ExtractionContext { entity, transform };
+                let params =
+                    Extractable::get_params_with_context(params_item, &resource_params, &context);
+                let frame = <Extractable::Shader as ParameterizedShader>::frame(&params);
+                let instance_transform = Extractable::instance_transform(&params, &context);
+                Some(instance_aabb(frame, transform, &instance_transform))
+            }
+        };
+        changed.push((entity, new_aabb));
+    }
+
+    let mut aabbs = shapes.p1();
+    for (entity, new_aabb) in changed.drain(..) {
+        match (aabbs.get_mut(entity), new_aabb) {
+            (Ok(mut aabb), Some(new_aabb)) => {
+                // avoid triggering change detection when nothing has changed
+                aabb.set_if_neq(new_aabb);
+            }
+            (Ok(_), None) => {
+                commands.entity(entity).remove::<Aabb>();
+            }
+            (Err(_), Some(new_aabb)) => {
+                commands.entity(entity).insert(new_aabb);
+            }
+            (Err(_), None) => {}
+        }
+    }
+}
+
+/// The bounds, in the entity's local space, of a shape drawn with this transform.
+/// The shape may be drawn with a different transform to its entity, and only the parts of the transform used by the vertex shader are taken into account
+fn instance_aabb(
+    frame: Frame,
+    entity_transform: &GlobalTransform,
+    instance_transform: &GlobalTransform,
+) -> Aabb {
+    Aabb::enclosing(instance_corners(
+        frame,
+        entity_transform,
+        instance_transform,
+    ))
+    .unwrap_or_default()
+}
+
+/// The corners, in the entity's local space, of the quad of a shape drawn with this transform
+pub(crate) fn instance_corners(
+    frame: Frame,
+    entity_transform: &GlobalTransform,
+    instance_transform: &GlobalTransform,
+) -> [Vec3; 4] {
+    // the frame is applied and then the quad is rotated and uniformly scaled, as in the vertex shader
+    let rotation_and_scale = instance_transform.affine().transform_vector3(Vec3::X).xy();
+    let half_size = Vec2::new(frame.half_width, frame.half_height);
+    let to_local = entity_transform.affine().inverse();
+
+    [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+        let offset = rotation_and_scale.rotate(half_size * Vec2::new(x, y));
+        let corner = instance_transform.translation_vec3a() + Vec3A::from(offset.extend(0.0));
+        Vec3::from(to_local.transform_point3a(corner))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::System;
+
+    use super::*;
+    use crate::{
+        primitives::{CircleShader, ShaderColor},
+        shader_params::NoParams,
+        ShapeVertex,
+    };
+
+    #[test]
+    fn aabbs_are_only_updated_for_changed_shapes() {
+        let mut world = World::new();
+        world.init_resource::<ShapeCulling>();
+        world.init_resource::<ParamComponents<CircleShader>>();
+        let entity = world
+            .spawn((
+                ShaderColor::<0>::default(),
+                GlobalTransform::default(),
+                ShaderUsage::<CircleShader>::default(),
+            ))
+            .id();
+
+        let mut system = IntoSystem::into_system(update_shape_aabbs::<CircleShader>);
+        system.initialize(&mut world);
+        let mut run = |world: &mut World| {
+            system.run((), world);
+            system.apply_deferred(world);
+            *world.get::<Aabb>(entity).unwrap()
+        };
+
+        let square = run(&mut world);
+        assert_eq!(square.half_extents, Vec3A::new(1.0, 1.0, 0.0));
+
+        // an unchanged shape keeps its bounds
+        let marker = Aabb::from_min_max(Vec3::ZERO, Vec3::ONE);
+        *world.get_mut::<Aabb>(entity).unwrap() = marker;
+        assert_eq!(run(&mut world), marker);
+
+        world.get_mut::<ShaderColor<0>>(entity).unwrap().color = LinearRgba::RED;
+        assert_eq!(run(&mut world), square);
+    }
+
+    #[test]
+    fn vertex_bounds_enclose_rotated_quad() {
+        let frame = Frame {
+            half_width: 1.0,
+            half_height: 0.25,
+        };
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(10.0, -5.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(1.0))
+                .with_scale(Vec3::splat(20.0)),
+        );
+
+        let corners = instance_corners(frame, &GlobalTransform::IDENTITY, &transform);
+        let expected = corners.iter().fold(
+            Rect::from_center_size(corners[0].xy(), Vec2::ZERO),
+            |rect, corner| rect.union_point(corner.xy()),
+        );
+        let bounds = ShapeVertex::new(&transform, NoParams).bounds(frame);
+
+        assert!((bounds.min - expected.min).abs().max_element() < 1e-4);
+        assert!((bounds.max - expected.max).abs().max_element() < 1e-4);
+    }
+
+    #[test]
+    fn vertical_segment_keeps_its_width() {
+        // a quad with a non-square frame rotated by 90 degrees is tall and thin
+        let frame = Frame {
+            half_width: 1.0,
+            half_height: 0.1,
+        };
+        let transform = GlobalTransform::from(
+            Transform::from_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2))
+                .with_scale(Vec3::splat(50.0)),
+        );
+
+        let bounds = ShapeVertex::new(&transform, NoParams).bounds(frame);
+
+        assert!(
+            (bounds.half_size() - Vec2::new(5.0, 50.0))
+                .abs()
+                .max_element()
+                < 1e-3
+        );
+    }
+}
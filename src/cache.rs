@@ -0,0 +1,934 @@
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::core_2d::Transparent2d,
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        query::ROQueryItem,
+        system::{
+            lifetimeless::{Read, SRes},
+            StaticSystemParam, SystemParamItem,
+        },
+    },
+    math::{FloatOrd, Vec3Swizzles},
+    prelude::*,
+    render::{
+        camera::{CameraOutputMode, CameraUpdateSystem, Viewport},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+            SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, BlendState,
+            BufferUsages, ColorTargetState, ColorWrites, Face, FragmentState, FrontFace,
+            MultisampleState, PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology,
+            RawBufferVec, RenderPipelineDescriptor, SamplerBindingType, ShaderStages,
+            SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat,
+            TextureSampleType, VertexAttribute, VertexBufferLayout, VertexFormat, VertexState,
+            VertexStepMode,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{BevyDefault, GpuImage},
+        view::{
+            ExtractedView, RenderLayers, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms,
+        },
+        Extract, Render, RenderApp, RenderSet,
+    },
+    transform::TransformSystem,
+    utils::HashMap,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    frame::Frame,
+    is_visible_in_view,
+    parameterized_shader::{ExtractToShader, ExtractionContext},
+    pipeline_key::PipelineKey,
+    shader_params::ShaderParams,
+    shader_pipeline::ShaderPipelineKey,
+    snapshot::{render_target_image, snapshot_camera, ShapeSnapshotCamera},
+    sorting::{image_sort_group, ShapeSortInputs, SortedInstances},
+    ParamShaderSortMode, ShaderUsage, ShaderYSortOffset, ShaderZIndex, ShapeBatch, ShapeVertex,
+    ViewShapeBatches, ViewVisibleShapes, ViewsAwaitingPipelines,
+};
+
+/// Draws a shape from a texture which is only rendered again when the shape changes.
+///
+/// This is useful for shapes with expensive fragment shaders that rarely change.
+/// The texture is rendered again when the params or frame change, or when the scale changes by more than `rebake_scale_factor`.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct ShaderCacheToTexture {
+    /// How far the shape reaches, in the same units as the shader's frame expression
+    pub frame: Frame,
+    /// Texture pixels per world unit
+    pub resolution: f32,
+    /// The shape will be rendered to the texture again if its scale is multiplied or divided by more than this
+    pub rebake_scale_factor: f32,
+}
+
+impl Default for ShaderCacheToTexture {
+    fn default() -> Self {
+        Self {
+            frame: Frame::default(),
+            resolution: 1.0,
+            rebake_scale_factor: 1.25,
+        }
+    }
+}
+
+impl ShaderCacheToTexture {
+    /// The number of shapes drawn from a cached texture this frame
+    pub const CACHE_HITS: DiagnosticPath = DiagnosticPath::const_new("param_shaders/cache_hits");
+    /// The number of cached shapes which had to be drawn with their shader this frame
+    pub const CACHE_MISSES: DiagnosticPath =
+        DiagnosticPath::const_new("param_shaders/cache_misses");
+}
+
+const CACHED_SHAPE_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5c6f3f8e1d2b4a7c9e0f1a2b3c4d5e6f);
+
+/// The size of each atlas page in pixels
+const ATLAS_PAGE_SIZE: u32 = 2048;
+/// The smallest size of an atlas cell in pixels
+const MIN_CELL_SIZE: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub(crate) enum ShaderCacheSystems {
+    Update,
+    Bake,
+}
+
+pub(crate) struct ShaderCachePlugin;
+
+impl Plugin for ShaderCachePlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            CACHED_SHAPE_SHADER_HANDLE,
+            "shaders/cached_shape.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.init_resource::<ShaderCacheAtlas>()
+            .register_diagnostic(Diagnostic::new(ShaderCacheToTexture::CACHE_HITS))
+            .register_diagnostic(Diagnostic::new(ShaderCacheToTexture::CACHE_MISSES))
+            .configure_sets(
+                PostUpdate,
+                (
+                    ShaderCacheSystems::Update.after(TransformSystem::TransformPropagate),
+                    ShaderCacheSystems::Bake
+                        .after(ShaderCacheSystems::Update)
+                        .before(CameraUpdateSystem),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                bake_shader_caches.in_set(ShaderCacheSystems::Bake),
+            );
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .add_render_command::<Transparent2d, DrawCachedShape>()
+                .init_resource::<ExtractedCachedShapes>()
+                .init_resource::<SpecializedRenderPipelines<CachedShapePipeline>>()
+                .add_systems(ExtractSchedule, extract_cached_shapes)
+                .add_systems(
+                    Render,
+                    (
+                        queue_cached_shapes.in_set(RenderSet::Queue),
+                        prepare_cached_shapes.in_set(RenderSet::PrepareBindGroups),
+                        cleanup_cached_shapes.in_set(RenderSet::Cleanup),
+                    ),
+                );
+        };
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.get_sub_app_mut(RenderApp)
+            .unwrap()
+            .init_resource::<CachedShapePipeline>();
+    }
+}
+
+/// What a cached shape looked like when it was drawn to its texture
+#[derive(Debug, Clone, PartialEq)]
+struct BakedShape {
+    params: Vec<u8>,
+    frame: Frame,
+    scale: f32,
+}
+
+impl BakedShape {
+    /// Whether a texture baked from this can be used to draw `other`
+    fn can_draw(&self, other: &Self, rebake_scale_factor: f32) -> bool {
+        let scale_ratio = other.scale / self.scale;
+        self.params == other.params
+            && self.frame == other.frame
+            && scale_ratio <= rebake_scale_factor
+            && scale_ratio >= rebake_scale_factor.recip()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CacheStatus {
+    /// The shape needs to be drawn to its texture
+    Dirty(BakedShape),
+    /// The shape is being drawn to its texture by the camera
+    Baking { camera: Entity, shape: BakedShape },
+    /// The texture is up to date
+    Ready(BakedShape),
+}
+
+impl CacheStatus {
+    fn shape(&self) -> &BakedShape {
+        match self {
+            CacheStatus::Dirty(shape) => shape,
+            CacheStatus::Baking { shape, .. } => shape,
+            CacheStatus::Ready(shape) => shape,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct ShaderCacheState {
+    status: CacheStatus,
+}
+
+impl ShaderCacheState {
+    /// Whether the shape should be drawn from its texture
+    pub(crate) fn is_ready(&self) -> bool {
+        matches!(self.status, CacheStatus::Ready(_))
+    }
+}
+
+fn transform_scale(transform: &GlobalTransform) -> f32 {
+    transform.affine().transform_vector3(Vec3::X).xy().length()
+}
+
+/// Marks cached shapes as dirty when they no longer match their texture
+pub(crate) fn update_shader_caches<'w, Extractable: ExtractToShader>(
+    mut commands: Commands,
+    mut shapes: Query<
+        (
+            Entity,
+            &ShaderCacheToTexture,
+            Option<&mut ShaderCacheState>,
+            Extractable::ParamsQuery<'static>,
+            &GlobalTransform,
+        ),
+        (With<ShaderUsage<Extractable>>, Extractable::Filter),
+    >,
+    resource_params: StaticSystemParam<Extractable::ResourceParams<'w>>,
+) {
+    for (entity, cache, state, params_item, transform) in shapes.iter_mut() {
+        let context = ExtractionContext { entity, transform };
+        let params = Extractable::get_params_with_context(params_item, &resource_params, &context);
+        let wanted = BakedShape {
+            params: bytemuck::bytes_of(&params).to_vec(),
+            frame: cache.frame,
+            scale: transform_scale(transform),
+        };
+
+        match state {
+            Some(mut state) => {
+                if !state
+                    .status
+                    .shape()
+                    .can_draw(&wanted, cache.rebake_scale_factor)
+                {
+                    state.status = CacheStatus::Dirty(wanted);
+                }
+            }
+            None => {
+                commands.entity(entity).insert(ShaderCacheState {
+                    status: CacheStatus::Dirty(wanted),
+                });
+            }
+        }
+    }
+}
+
+/// Spawns cameras to draw dirty shapes to their textures
+fn bake_shader_caches(
+    mut commands: Commands,
+    mut atlas: ResMut<ShaderCacheAtlas>,
+    mut images: ResMut<Assets<Image>>,
+    mut finished_cameras: RemovedComponents<ShapeSnapshotCamera>,
+    mut removed_caches: RemovedComponents<ShaderCacheToTexture>,
+    mut shapes: Query<(
+        Entity,
+        &ShaderCacheToTexture,
+        &mut ShaderCacheState,
+        &GlobalTransform,
+        &InheritedVisibility,
+        Option<&RenderLayers>,
+    )>,
+    mut diagnostics: Diagnostics,
+) {
+    for entity in removed_caches.read() {
+        atlas.release(entity);
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.remove::<ShaderCacheState>();
+        }
+    }
+
+    let finished_cameras: EntityHashSet = finished_cameras.read().collect();
+
+    let mut hits = 0;
+    let mut misses = 0;
+
+    for (entity, cache, mut state, transform, inherited_visibility, layers) in shapes.iter_mut() {
+        match &state.status {
+            CacheStatus::Baking { camera, shape } if finished_cameras.contains(camera) => {
+                state.status = CacheStatus::Ready(shape.clone());
+            }
+            // a hidden shape won't be drawn by the camera
+            CacheStatus::Dirty(shape) if inherited_visibility.get() => {
+                let shape = shape.clone();
+                let area_size =
+                    Vec2::new(cache.frame.half_width, cache.frame.half_height) * 2.0 * shape.scale;
+                let cell = atlas.allocate(entity, area_size * cache.resolution, &mut images);
+                let rect = atlas.cell_rect(cell);
+
+                let rotation = transform.affine().transform_vector3(Vec3::X).xy();
+                let camera_transform = Transform::from_translation(transform.translation())
+                    .with_rotation(Quat::from_rotation_z(rotation.to_angle()));
+
+                let mut camera = snapshot_camera(
+                    atlas.pages[cell.page].image.clone(),
+                    area_size,
+                    camera_transform,
+                    Color::NONE,
+                );
+                camera.camera.viewport = Some(Viewport {
+                    physical_position: rect.min,
+                    physical_size: rect.size(),
+                    ..default()
+                });
+                // Only write to this cell of the atlas
+                camera.camera.output_mode = CameraOutputMode::Write {
+                    blend_state: None,
+                    clear_color: ClearColorConfig::None,
+                };
+                // Cameras with the same target need different orders
+                camera.camera.order = atlas.next_camera_order();
+
+                let camera = commands
+                    .spawn((
+                        camera,
+                        layers.cloned().unwrap_or_default(),
+                        ShapeSnapshotCamera::new(
+                            atlas.pages[cell.page].image.clone(),
+                            Some(EntityHashSet::from_iter([entity])),
+                        ),
+                    ))
+                    .id();
+
+                state.status = CacheStatus::Baking { camera, shape };
+            }
+            _ => {}
+        }
+
+        if state.is_ready() {
+            hits += 1;
+        } else {
+            misses += 1;
+        }
+    }
+
+    diagnostics.add_measurement(&ShaderCacheToTexture::CACHE_HITS, || hits as f64);
+    diagnostics.add_measurement(&ShaderCacheToTexture::CACHE_MISSES, || misses as f64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AtlasCell {
+    page: usize,
+    index: u32,
+}
+
+#[derive(Debug)]
+struct AtlasPage {
+    image: Handle<Image>,
+    cell_size: UVec2,
+    used: Vec<bool>,
+}
+
+impl AtlasPage {
+    fn columns(&self) -> u32 {
+        ATLAS_PAGE_SIZE / self.cell_size.x
+    }
+}
+
+/// Textures that cached shapes are drawn to.
+/// Each page is split into cells of the same size and each cached shape has one cell.
+#[derive(Debug, Resource, Default)]
+pub(crate) struct ShaderCacheAtlas {
+    pages: Vec<AtlasPage>,
+    cells: EntityHashMap<AtlasCell>,
+    camera_order: isize,
+}
+
+impl ShaderCacheAtlas {
+    /// Get a cell big enough for `size` pixels
+    fn allocate(&mut self, entity: Entity, size: Vec2, images: &mut Assets<Image>) -> AtlasCell {
+        let cell_size = size
+            .ceil()
+            .as_uvec2()
+            .clamp(UVec2::splat(MIN_CELL_SIZE), UVec2::splat(ATLAS_PAGE_SIZE))
+            .to_array()
+            .map(u32::next_power_of_two);
+        let cell_size = UVec2::from_array(cell_size);
+
+        if let Some(cell) = self.cells.get(&entity) {
+            if self.pages[cell.page].cell_size == cell_size {
+                return *cell;
+            }
+            self.release(entity);
+        }
+
+        let free = self.pages.iter().enumerate().find_map(|(page, p)| {
+            if p.cell_size != cell_size {
+                return None;
+            }
+            let index = p.used.iter().position(|used| !used)?;
+            Some(AtlasCell {
+                page,
+                index: index as u32,
+            })
+        });
+
+        let cell = free.unwrap_or_else(|| {
+            let cells = (ATLAS_PAGE_SIZE / cell_size.x) * (ATLAS_PAGE_SIZE / cell_size.y);
+            self.pages.push(AtlasPage {
+                image: images.add(render_target_image(UVec2::splat(ATLAS_PAGE_SIZE))),
+                cell_size,
+                used: vec![false; cells as usize],
+            });
+            AtlasCell {
+                page: self.pages.len() - 1,
+                index: 0,
+            }
+        });
+
+        self.pages[cell.page].used[cell.index as usize] = true;
+        self.cells.insert(entity, cell);
+        cell
+    }
+
+    fn release(&mut self, entity: Entity) {
+        if let Some(cell) = self.cells.remove(&entity) {
+            self.pages[cell.page].used[cell.index as usize] = false;
+        }
+    }
+
+    /// The pixels of the page covered by this cell
+    fn cell_rect(&self, cell: AtlasCell) -> URect {
+        let page = &self.pages[cell.page];
+        let columns = page.columns();
+        let min = UVec2::new(cell.index % columns, cell.index / columns) * page.cell_size;
+        URect::from_corners(min, min + page.cell_size)
+    }
+
+    fn next_camera_order(&mut self) -> isize {
+        self.camera_order = self.camera_order.wrapping_add(1);
+        self.camera_order
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, Pod, Zeroable)]
+pub(crate) struct CachedShapeParams {
+    half_size: Vec2,
+    uv_min: Vec2,
+    uv_max: Vec2,
+}
+
+impl ShaderParams for CachedShapeParams {}
+
+#[derive(Debug, Clone)]
+struct CachedShapeInstance {
+    entity: Entity,
+    layers: RenderLayers,
+    sort: ShapeSortInputs,
+    bounds: Rect,
+    page: AssetId<Image>,
+    vertex: ShapeVertex<CachedShapeParams>,
+}
+
+#[derive(Resource)]
+struct ExtractedCachedShapes {
+    instances: Vec<CachedShapeInstance>,
+    /// The order of `instances` for each sort mode used by a view
+    sorted: SortedInstances,
+    vertices: RawBufferVec<ShapeVertex<CachedShapeParams>>,
+    view_bind_group: Option<BindGroup>,
+    texture_bind_groups: HashMap<AssetId<Image>, BindGroup>,
+}
+
+impl Default for ExtractedCachedShapes {
+    fn default() -> Self {
+        Self {
+            instances: Default::default(),
+            sorted: Default::default(),
+            vertices: RawBufferVec::new(BufferUsages::VERTEX),
+            view_bind_group: None,
+            texture_bind_groups: Default::default(),
+        }
+    }
+}
+
+fn extract_cached_shapes(
+    mut extracted: ResMut<ExtractedCachedShapes>,
+    atlas: Extract<Res<ShaderCacheAtlas>>,
+    shapes: Extract<
+        Query<(
+            Entity,
+            &ViewVisibility,
+            &GlobalTransform,
+            &ShaderCacheToTexture,
+            &ShaderCacheState,
+            Option<&RenderLayers>,
+            Option<&ShaderZIndex>,
+            Option<&ShaderYSortOffset>,
+        )>,
+    >,
+) {
+    let page_size = Vec2::splat(ATLAS_PAGE_SIZE as f32);
+
+    for (entity, view_visibility, transform, cache, state, layers, z_index, y_offset) in
+        shapes.iter()
+    {
+        if !view_visibility.get() || !state.is_ready() {
+            continue;
+        }
+        let Some(cell) = atlas.cells.get(&entity) else {
+            continue;
+        };
+        let rect = atlas.cell_rect(*cell).as_rect();
+
+        let params = CachedShapeParams {
+            half_size: Vec2::new(cache.frame.half_width, cache.frame.half_height),
+            uv_min: rect.min / page_size,
+            uv_max: rect.max / page_size,
+        };
+
+        let vertex = ShapeVertex::new(transform, params);
+
+        extracted.instances.push(CachedShapeInstance {
+            entity,
+            layers: layers.cloned().unwrap_or_default(),
+            sort: ShapeSortInputs::new(transform, z_index, y_offset),
+            bounds: vertex.bounds(cache.frame),
+            page: atlas.pages[cell.page].image.id(),
+            vertex,
+        });
+    }
+}
+
+fn queue_cached_shapes(
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    pipeline: Res<CachedShapePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<CachedShapePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    msaa: Res<Msaa>,
+    mut extracted: ResMut<ExtractedCachedShapes>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    mut views_awaiting_pipelines: ResMut<ViewsAwaitingPipelines>,
+    mut view_batches: ResMut<ViewShapeBatches>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        Option<&ViewVisibleShapes>,
+        Option<&RenderLayers>,
+        Option<&ParamShaderSortMode>,
+    )>,
+) {
+    let extracted = extracted.as_mut();
+    extracted.vertices.clear();
+    extracted.sorted.sort(
+        views
+            .iter()
+            .map(|(.., mode)| mode.copied().unwrap_or_default()),
+        &extracted.instances,
+        |item, mode| {
+            (
+                item.sort.key(mode),
+                image_sort_group(Some(item.page)),
+                item.entity,
+            )
+        },
+        |item| {
+            extracted.vertices.push(item.vertex);
+        },
+    );
+
+    let draw_function = draw_functions.read().id::<DrawCachedShape>();
+    view_batches.register_draw_function(draw_function);
+
+    for (view_entity, view, visible_shapes, view_layers, sort_mode) in views.iter() {
+        let Some(transparent_phase) = transparent_render_phases.get_mut(&view_entity) else {
+            continue;
+        };
+        let view_layers = view_layers.cloned().unwrap_or_default();
+
+        let specialize_key = ShaderPipelineKey {
+            mesh: PipelineKey::from_msaa_samples(msaa.samples())
+                | PipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip),
+            hdr: view.hdr,
+        };
+        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, specialize_key);
+
+        let instances = &extracted.instances;
+
+        let is_drawn = |instance: &CachedShapeInstance| {
+            is_visible_in_view(
+                instance.entity,
+                &instance.layers,
+                visible_shapes,
+                &view_layers,
+            )
+        };
+
+        let sort_mode = sort_mode.copied().unwrap_or_default();
+        let mut sorted = extracted.sorted.iter(sort_mode, instances).peekable();
+
+        // views only wait for the pipeline when they draw some of its shapes
+        let mut draws_shapes = false;
+        while let Some((start, first_shape)) = sorted.next() {
+            if !is_drawn(first_shape) {
+                continue;
+            }
+            draws_shapes = true;
+            let sort_key = first_shape.sort.key(sort_mode);
+            let mut end = start + 1;
+            let mut bounds = first_shape.bounds;
+            // shapes on the same page with the same sort key can be drawn together
+            while let Some((index, shape)) = sorted.next_if(|(_, n)| {
+                n.sort.key(sort_mode) == sort_key && n.page == first_shape.page && is_drawn(n)
+            }) {
+                end = index + 1;
+                bounds = bounds.union(shape.bounds);
+            }
+
+            let extra_index = view_batches.push(
+                view_entity,
+                ShapeBatch {
+                    range: start..end,
+                    order: sort_key.order,
+                    image: Some(first_shape.page),
+                    bounds,
+                    culled: None,
+                },
+            );
+
+            transparent_phase.add(Transparent2d {
+                draw_function,
+                pipeline,
+                entity: first_shape.entity,
+                sort_key: FloatOrd(sort_key.depth),
+                batch_range: 0..1,
+                extra_index,
+            });
+        }
+
+        if draws_shapes {
+            views_awaiting_pipelines.check(view_entity, &pipeline_cache, pipeline);
+        }
+    }
+}
+
+fn prepare_cached_shapes(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    view_uniforms: Res<ViewUniforms>,
+    pipeline: Res<CachedShapePipeline>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    mut extracted: ResMut<ExtractedCachedShapes>,
+) {
+    let Some(view_binding) = view_uniforms.uniforms.binding() else {
+        return;
+    };
+    let extracted = extracted.as_mut();
+
+    extracted.view_bind_group = Some(render_device.create_bind_group(
+        "cached_shape_view_bind_group",
+        &pipeline.view_layout,
+        &BindGroupEntries::single(view_binding),
+    ));
+
+    extracted.texture_bind_groups.clear();
+    for instance in extracted.instances.iter() {
+        if extracted.texture_bind_groups.contains_key(&instance.page) {
+            continue;
+        }
+        let Some(gpu_image) = gpu_images.get(instance.page) else {
+            continue;
+        };
+        extracted.texture_bind_groups.insert(
+            instance.page,
+            render_device.create_bind_group(
+                "cached_shape_texture_bind_group",
+                &pipeline.texture_layout,
+                &BindGroupEntries::sequential((&gpu_image.texture_view, &gpu_image.sampler)),
+            ),
+        );
+    }
+
+    extracted
+        .vertices
+        .write_buffer(&render_device, &render_queue);
+}
+
+fn cleanup_cached_shapes(mut extracted: ResMut<ExtractedCachedShapes>) {
+    extracted.instances.clear();
+    extracted.sorted.clear();
+    extracted.vertices.clear();
+}
+
+type DrawCachedShape = (
+    SetItemPipeline,
+    SetCachedShapeViewBindGroup<0>,
+    SetCachedShapeTextureBindGroup<1>,
+    DrawCachedShapeBatch,
+);
+
+struct SetCachedShapeViewBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetCachedShapeViewBindGroup<I> {
+    type Param = SRes<ExtractedCachedShapes>;
+    type ViewQuery = Read<ViewUniformOffset>;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        view_uniform: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        extracted: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = extracted.into_inner().view_bind_group.as_ref() else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, bind_group, &[view_uniform.offset]);
+        RenderCommandResult::Success
+    }
+}
+
+struct SetCachedShapeTextureBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetCachedShapeTextureBindGroup<I> {
+    type Param = (SRes<ExtractedCachedShapes>, SRes<ViewShapeBatches>);
+    type ViewQuery = Entity;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: Entity,
+        _entity: Option<()>,
+        (extracted, view_batches): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = view_batches
+            .get(view, item.extra_index())
+            .and_then(|batch| batch.image)
+            .and_then(|page| extracted.into_inner().texture_bind_groups.get(&page))
+        else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct DrawCachedShapeBatch;
+impl<P: PhaseItem> RenderCommand<P> for DrawCachedShapeBatch {
+    type Param = (SRes<ExtractedCachedShapes>, SRes<ViewShapeBatches>);
+    type ViewQuery = Entity;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: Entity,
+        _entity: Option<()>,
+        (extracted, view_batches): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (Some(batch), Some(buffer)) = (
+            view_batches.into_inner().get(view, item.extra_index()),
+            extracted.into_inner().vertices.buffer(),
+        ) else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_vertex_buffer(0, buffer.slice(..));
+        pass.draw(0..4, batch.range.clone());
+        RenderCommandResult::Success
+    }
+}
+
+#[derive(Resource)]
+struct CachedShapePipeline {
+    view_layout: BindGroupLayout,
+    texture_layout: BindGroupLayout,
+}
+
+impl FromWorld for CachedShapePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let view_layout = render_device.create_bind_group_layout(
+            "cached_shape_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX_FRAGMENT,
+                uniform_buffer::<ViewUniform>(true),
+            ),
+        );
+
+        let texture_layout = render_device.create_bind_group_layout(
+            "cached_shape_texture_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
+        Self {
+            view_layout,
+            texture_layout,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for CachedShapePipeline {
+    type Key = ShaderPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let formats = [
+            VertexFormat::Float32x2, // rotation
+            VertexFormat::Float32x3, // position
+            VertexFormat::Float32,   // scale
+            VertexFormat::Float32x2, // half_size
+            VertexFormat::Float32x2, // uv_min
+            VertexFormat::Float32x2, // uv_max
+        ];
+
+        let mut offset = 0;
+        let mut attributes = Vec::with_capacity(formats.len());
+        for (shader_location, format) in (0..).zip(formats) {
+            attributes.push(VertexAttribute {
+                format,
+                offset,
+                shader_location,
+            });
+            offset += format.size();
+        }
+
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: CACHED_SHAPE_SHADER_HANDLE,
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: vec![VertexBufferLayout {
+                    array_stride: offset,
+                    step_mode: VertexStepMode::Instance,
+                    attributes,
+                }],
+            },
+            fragment: Some(FragmentState {
+                shader: CACHED_SHAPE_SHADER_HANDLE,
+                entry_point: "fragment".into(),
+                shader_defs: Vec::new(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    // the texture was drawn with alpha blending onto a transparent background
+                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            layout: vec![self.view_layout.clone(), self.texture_layout.clone()],
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: key.mesh.primitive_topology(),
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.mesh.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("cached_shape_pipeline".into()),
+            push_constant_ranges: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_of_the_same_size_share_a_page() {
+        let mut images = Assets::<Image>::default();
+        let mut atlas = ShaderCacheAtlas::default();
+
+        let cells: Vec<AtlasCell> = (0..17)
+            .map(|index| {
+                atlas.allocate(Entity::from_raw(index), Vec2::new(100.0, 50.0), &mut images)
+            })
+            .collect();
+        assert_eq!(atlas.pages.len(), 1);
+
+        // the cells are rounded up to 128x64 pixels, so there are 16 on each row
+        assert_eq!(
+            atlas.cell_rect(cells[1]),
+            URect::from_corners(UVec2::new(128, 0), UVec2::new(256, 64))
+        );
+        assert_eq!(
+            atlas.cell_rect(cells[16]),
+            URect::from_corners(UVec2::new(0, 64), UVec2::new(128, 128))
+        );
+    }
+
+    #[test]
+    fn cells_of_different_sizes_use_different_pages() {
+        let mut images = Assets::<Image>::default();
+        let mut atlas = ShaderCacheAtlas::default();
+
+        let small = atlas.allocate(Entity::from_raw(0), Vec2::splat(1.0), &mut images);
+        let large = atlas.allocate(Entity::from_raw(1), Vec2::splat(300.0), &mut images);
+
+        assert_ne!(small.page, large.page);
+        assert_eq!(atlas.cell_rect(small).size(), UVec2::splat(MIN_CELL_SIZE));
+        assert_eq!(atlas.cell_rect(large).size(), UVec2::splat(512));
+    }
+
+    #[test]
+    fn cells_are_kept_until_the_shape_changes_size() {
+        let mut images = Assets::<Image>::default();
+        let mut atlas = ShaderCacheAtlas::default();
+        let first = Entity::from_raw(0);
+        let second = Entity::from_raw(1);
+
+        let cell = atlas.allocate(first, Vec2::splat(10.0), &mut images);
+        // sizes which round up to the same cell size keep the cell
+        assert_eq!(atlas.allocate(first, Vec2::splat(16.0), &mut images), cell);
+
+        // the cell is freed when the shape needs a bigger one, and can be used by another shape
+        atlas.allocate(first, Vec2::splat(17.0), &mut images);
+        assert_eq!(atlas.allocate(second, Vec2::splat(10.0), &mut images), cell);
+
+        atlas.release(second);
+        assert_eq!(atlas.allocate(second, Vec2::splat(10.0), &mut images), cell);
+    }
+}
@@ -0,0 +1,542 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use bevy::{
+    ecs::{query::WorldQuery, system::SystemParam},
+    math::UVec4,
+    prelude::GlobalTransform,
+    reflect::{utility::GenericTypePathCell, Reflect, TypePath},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    frame::Frame,
+    helpers::{format_params_fields, format_params_reads},
+    parameterized_shader::{
+        ExtractToShader, ExtractionContext, FragmentImport, ParameterizedShader,
+    },
+    shader_params::ShaderParams,
+    shader_uniforms::{NoUniforms, ShaderUniforms},
+};
+
+/// Draws shapes using any of a set of shaders with a single pipeline.
+///
+/// Shapes drawn with different shaders can then be drawn in the same batch, even when they are interleaved with each other.
+/// `Shaders` is a tuple of shaders, such as `(RectShader, CircleShader)`.
+/// The fragment shader switches to the body of the right shader for each shape, so this is best suited to cheap shaders.
+///
+/// Shapes are drawn with this shader by using a [`CombinedExtraction`].
+pub struct CombinedShader<Shaders: ShaderSet>(PhantomData<Shaders>);
+
+impl<Shaders: ShaderSet> TypePath for CombinedShader<Shaders> {
+    fn type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| {
+            format!(
+                "bevy_param_shaders::combined::CombinedShader<{}>",
+                Shaders::type_paths()
+            )
+        })
+    }
+
+    fn short_type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| format!("CombinedShader<{}>", Shaders::short_type_paths()))
+    }
+}
+
+impl<Shaders: ShaderSet> ParameterizedShader for CombinedShader<Shaders> {
+    type Params = CombinedParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        let cases: String = (0..Shaders::LEN)
+            .map(|index| {
+                format!(
+                    "case {index}u: {{ return combined_fragment_{index}(in.pos, in.data0, in.data1, in.data2, in.data3); }}\n"
+                )
+            })
+            .collect();
+
+        format!(
+            r#"switch in.shader {{
+{cases}default: {{ return vec4<f32>(0.0); }}
+}}"#
+        )
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        "combined_frame(vertex)"
+    }
+
+    fn frame(params: &Self::Params) -> Frame {
+        Shaders::frame(params)
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        Shaders::imports().into_iter()
+    }
+
+    fn vertex_functions() -> impl Into<String> {
+        let cases: String = (0..Shaders::LEN)
+            .map(|index| format!("case {index}u: {{ return combined_frame_{index}(instance); }}\n"))
+            .collect();
+
+        format!(
+            r#"{}
+
+fn combined_frame(instance: Instance) -> vec2<f32> {{
+switch instance.shader {{
+{cases}default: {{ return vec2<f32>(0.0); }}
+}}
+}}"#,
+            Shaders::vertex_functions()
+        )
+    }
+
+    fn fragment_functions() -> impl Into<String> {
+        Shaders::fragment_functions()
+    }
+
+    const USE_TIME: bool = Shaders::USE_TIME;
+
+    const UUID: u128 = Shaders::UUID;
+}
+
+/// The params of a [`CombinedShader`]: the index of the shader to use and that shader's params
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, Pod, Zeroable)]
+pub struct CombinedParams {
+    pub shader: u32,
+    pub data0: UVec4,
+    pub data1: UVec4,
+    pub data2: UVec4,
+    pub data3: UVec4,
+}
+
+impl ShaderParams for CombinedParams {}
+
+impl CombinedParams {
+    /// The largest params, in bytes, of a shader that can be combined
+    pub const MAX_PARAMS_SIZE: usize = 64;
+
+    /// Panics if the params are larger than [`Self::MAX_PARAMS_SIZE`]
+    pub fn new<PARAMS: ShaderParams>(shader: u32, params: PARAMS) -> Self {
+        let bytes = bytemuck::bytes_of(&params);
+        let mut words = [0u32; Self::MAX_PARAMS_SIZE / 4];
+        bytemuck::cast_slice_mut::<u32, u8>(&mut words)[..bytes.len()].copy_from_slice(bytes);
+
+        Self {
+            shader,
+            data0: UVec4::from_slice(&words[0..4]),
+            data1: UVec4::from_slice(&words[4..8]),
+            data2: UVec4::from_slice(&words[8..12]),
+            data3: UVec4::from_slice(&words[12..16]),
+        }
+    }
+
+    /// The params of the shader, which must be the shader these params were created for
+    pub fn params<PARAMS: ShaderParams>(&self) -> PARAMS {
+        let words = [self.data0, self.data1, self.data2, self.data3];
+        let bytes: &[u8] = bytemuck::cast_slice(&words);
+        let mut params = PARAMS::zeroed();
+        let params_bytes = bytemuck::bytes_of_mut(&mut params);
+        params_bytes.copy_from_slice(&bytes[..params_bytes.len()]);
+        params
+    }
+}
+
+/// Extracts shapes using `Extract` and draws them with a [`CombinedShader`].
+///
+/// `Extract::Shader` must be one of `Shaders`, which is checked when the plugin is built.
+pub struct CombinedExtraction<Extract: ExtractToShader, Shaders: ShaderSet>(
+    PhantomData<(Extract, Shaders)>,
+);
+
+impl<Extract: ExtractToShader, Shaders: ShaderSet> ExtractToShader
+    for CombinedExtraction<Extract, Shaders>
+{
+    type Shader = CombinedShader<Shaders>;
+    type ParamsQuery<'a> = Extract::ParamsQuery<'a>;
+    type ParamsBundle = Extract::ParamsBundle;
+    type ResourceParams<'w> = Extract::ResourceParams<'w>;
+    type Filter = Extract::Filter;
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        CombinedParams::new(
+            Self::shader_index(),
+            Extract::get_params(query_item, resource),
+        )
+    }
+
+    fn get_params_with_context(
+        query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        context: &ExtractionContext,
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        CombinedParams::new(
+            Self::shader_index(),
+            Extract::get_params_with_context(query_item, resource, context),
+        )
+    }
+
+    fn validate() {
+        Extract::validate();
+        Shaders::validate();
+        Self::shader_index();
+    }
+
+    fn should_draw(params: &CombinedParams) -> bool {
+        Extract::should_draw(&params.params())
+    }
+
+    fn instance_transform(params: &CombinedParams, context: &ExtractionContext) -> GlobalTransform {
+        Extract::instance_transform(&params.params(), context)
+    }
+}
+
+impl<Extract: ExtractToShader, Shaders: ShaderSet> CombinedExtraction<Extract, Shaders> {
+    /// The index of `Extract::Shader` among `Shaders`, this is checked in `validate` so it does not panic while extracting
+    fn shader_index() -> u32 {
+        let Some(index) = Shaders::index_of(TypeId::of::<Extract::Shader>()) else {
+            panic!(
+                "{} cannot be extracted to {} because it is not one of the combined shaders",
+                <Extract::Shader as TypePath>::type_path(),
+                CombinedShader::<Shaders>::type_path()
+            );
+        };
+        index
+    }
+}
+
+/// A tuple of shaders which can be combined into a [`CombinedShader`]
+pub trait ShaderSet: Send + Sync + 'static {
+    /// The number of shaders in the set
+    const LEN: u32;
+    const UUID: u128;
+    const USE_TIME: bool;
+
+    /// The index of the shader in this set, which is used to choose the shader when drawing
+    fn index_of(shader: TypeId) -> Option<u32>;
+
+    /// Panics if the shaders cannot be combined, such as when one has uniforms or two define different functions with the same name
+    fn validate();
+
+    /// The frame of a shape drawn with these params
+    fn frame(params: &CombinedParams) -> Frame;
+
+    /// The imports of every shader, without duplicates
+    fn imports() -> Vec<FragmentImport>;
+
+    /// Functions which calculate the frame of each shader, after the vertex functions of every shader without duplicates
+    fn vertex_functions() -> String;
+
+    /// Functions which calculate the color of each shader, after the fragment functions of every shader without duplicates
+    fn fragment_functions() -> String;
+
+    fn type_paths() -> String;
+
+    fn short_type_paths() -> String;
+}
+
+/// The words of the params as an array, so that they can be read in the same way as the instance buffer
+fn format_words_array(prefix: &str) -> String {
+    let words = (0..4)
+        .flat_map(|data| ["x", "y", "z", "w"].map(|c| format!("{prefix}data{data}.{c}")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "array<u32, {}>({words})",
+        CombinedParams::MAX_PARAMS_SIZE / 4
+    )
+}
+
+/// Panics if the shader uses something which the combined shader cannot provide for each of its shaders
+fn check_can_combine<Shader: ParameterizedShader>() {
+    if Shader::Uniforms::ENABLED {
+        panic!(
+            "{} has uniforms, but only shaders without uniforms can be combined",
+            Shader::type_path()
+        );
+    }
+    if Shader::USE_TEXTURE {
+        panic!(
+            "{} uses a texture, but only shaders without textures can be combined",
+            Shader::type_path()
+        );
+    }
+
+    let size = std::mem::size_of::<Shader::Params>();
+    if size > CombinedParams::MAX_PARAMS_SIZE {
+        panic!(
+            "The params of {} are {size} bytes, but only shaders with params of at most {} bytes can be combined",
+            Shader::type_path(),
+            CombinedParams::MAX_PARAMS_SIZE
+        );
+    }
+}
+
+/// The top level definitions of a snippet of WGSL, such as functions and structs, in the order they appear
+fn definitions(functions: &str) -> Vec<&str> {
+    let mut definitions = vec![];
+    let mut depth = 0u32;
+    let mut start = 0;
+    let mut in_comment = false;
+    let mut chars = functions.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => in_comment = false,
+            _ if in_comment => continue,
+            '/' if chars.peek().is_some_and(|(_, next)| *next == '/') => in_comment = true,
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 && (c == '}' || c == ';') {
+            definitions.push(functions[start..=i].trim());
+            start = i + 1;
+        }
+    }
+    definitions.push(functions[start..].trim());
+    definitions.retain(|definition| !definition.is_empty() && *definition != ";");
+    definitions
+}
+
+/// The name of the function, struct, constant or alias defined by a top level definition
+fn definition_name(definition: &str) -> Option<&str> {
+    let mut words = definition
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .flat_map(|line| line.split(|c: char| !(c.is_alphanumeric() || c == '_')))
+        .filter(|word| !word.is_empty());
+    words.find(|word| matches!(*word, "fn" | "struct" | "const" | "alias"))?;
+    words.next()
+}
+
+/// The definitions of every shader's functions, leaving out definitions which are the same as earlier ones.
+/// Panics if two shaders define something with the same name differently, as the combined shader could not be compiled
+fn unique_definitions(functions: &[(&str, String)]) -> String {
+    let mut unique: Vec<(&str, &str)> = vec![];
+    for (shader, functions) in functions {
+        for definition in definitions(functions) {
+            if unique.iter().any(|(_, d)| *d == definition) {
+                continue;
+            }
+            if let Some(name) = definition_name(definition) {
+                if let Some((other, _)) = unique
+                    .iter()
+                    .find(|(_, d)| definition_name(d) == Some(name))
+                {
+                    panic!(
+                        "{other} and {shader} both define `{name}` differently, so they cannot be combined. Shared definitions must be identical in both shaders, or be moved to an import"
+                    );
+                }
+            }
+            unique.push((shader, definition));
+        }
+    }
+    unique
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn member_vertex_function<Shader: ParameterizedShader>(index: u32) -> String {
+    let fields = format_params_fields::<Shader::Params>();
+    let reads = format_params_reads(&Shader::Params::default(), "vertex", "words", 0);
+    let words = format_words_array("instance.");
+    let frame_expression: String = Shader::frame_expression().into();
+
+    format!(
+        r#"
+struct CombinedInstance{index} {{
+rotation: vec2<f32>,
+position: vec3<f32>,
+scale: f32,
+{fields}
+}};
+
+fn combined_frame_{index}(instance: Instance) -> vec2<f32> {{
+var words = {words};
+let base = 0u;
+var vertex: CombinedInstance{index};
+vertex.rotation = instance.rotation;
+vertex.position = instance.position;
+vertex.scale = instance.scale;
+{reads}
+return {frame_expression};
+}}
+"#
+    )
+}
+
+fn member_fragment_function<Shader: ParameterizedShader>(index: u32) -> String {
+    let fields = format_params_fields::<Shader::Params>();
+    let reads = format_params_reads(&Shader::Params::default(), "in", "words", 0);
+    let words = format_words_array("");
+    let fragment_body: String = Shader::fragment_body().into();
+
+    format!(
+        r#"
+struct CombinedFragmentInput{index} {{
+pos: vec2<f32>,
+{fields}
+}};
+
+fn combined_fragment_{index}(pos: vec2<f32>, data0: vec4<u32>, data1: vec4<u32>, data2: vec4<u32>, data3: vec4<u32>) -> vec4<f32> {{
+var words = {words};
+let base = 0u;
+var in: CombinedFragmentInput{index};
+in.pos = pos;
+{reads}
+{fragment_body}
+}}
+"#
+    )
+}
+
+macro_rules! impl_shader_set {
+    ($($index:tt: $shader:ident),*) => {
+        impl<$($shader: ParameterizedShader),*> ShaderSet for ($($shader,)*) {
+            const LEN: u32 = [$($index),*].len() as u32;
+
+            const UUID: u128 = {
+                let mut uuid = 0x5c1f8e2b7d3a4c69b0e4f7a2d8c6b193u128;
+                $(uuid = uuid.wrapping_mul(2875688479).wrapping_add($shader::UUID);)*
+                uuid
+            };
+
+            const USE_TIME: bool = false $(|| $shader::USE_TIME)*;
+
+            fn index_of(shader: TypeId) -> Option<u32> {
+                $(
+                    if shader == TypeId::of::<$shader>() {
+                        return Some($index);
+                    }
+                )*
+                None
+            }
+
+            fn validate() {
+                $(check_can_combine::<$shader>();)*
+                Self::vertex_functions();
+                Self::fragment_functions();
+            }
+
+            fn frame(params: &CombinedParams) -> Frame {
+                match params.shader {
+                    $($index => $shader::frame(&params.params::<$shader::Params>()),)*
+                    _ => Frame::square(0.0),
+                }
+            }
+
+            fn imports() -> Vec<FragmentImport> {
+                let mut imports: Vec<FragmentImport> = vec![];
+                $(
+                    for import in $shader::imports() {
+                        if !imports.iter().any(|i| i.import_path == import.import_path) {
+                            imports.push(import);
+                        }
+                    }
+                )*
+                imports
+            }
+
+            fn vertex_functions() -> String {
+                let functions = unique_definitions(&[
+                    $(($shader::type_path(), $shader::vertex_functions().into())),*
+                ]);
+                [functions, $(member_vertex_function::<$shader>($index)),*].join("\n")
+            }
+
+            fn fragment_functions() -> String {
+                let functions = unique_definitions(&[
+                    $(($shader::type_path(), $shader::fragment_functions().into())),*
+                ]);
+                [functions, $(member_fragment_function::<$shader>($index)),*].join("\n")
+            }
+
+            fn type_paths() -> String {
+                format!("({})", [$($shader::type_path()),*].join(", "))
+            }
+
+            fn short_type_paths() -> String {
+                format!("({})", [$($shader::short_type_path()),*].join(", "))
+            }
+        }
+    };
+}
+
+impl_shader_set!(0: A, 1: B);
+impl_shader_set!(0: A, 1: B, 2: C);
+impl_shader_set!(0: A, 1: B, 2: C, 3: D);
+impl_shader_set!(0: A, 1: B, 2: C, 3: D, 4: E);
+impl_shader_set!(0: A, 1: B, 2: C, 3: D, 4: E, 5: F);
+impl_shader_set!(0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G);
+impl_shader_set!(0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H);
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::App;
+
+    use super::*;
+    use crate::{
+        primitives::{CircleShader, RectShader, RectShaderExtraction, RoundedRectShader},
+        ExtractToShaderPlugin,
+    };
+
+    type Shaders = (RectShader, RoundedRectShader);
+
+    #[test]
+    fn shader_index_is_position_in_set() {
+        assert_eq!(
+            CombinedExtraction::<RectShaderExtraction, Shaders>::shader_index(),
+            0
+        );
+    }
+
+    const HELPER: &str = "
+// Distance to the edge of a unit square {}
+fn edge(pos: vec2<f32>) -> f32 {
+    return max(abs(pos.x), abs(pos.y));
+}
+";
+
+    #[test]
+    fn shared_definitions_are_only_included_once() {
+        let functions = unique_definitions(&[
+            ("A", format!("{HELPER}\nstruct A {{ x: f32 }};")),
+            (
+                "B",
+                format!("{HELPER}\nfn b() -> f32 {{ return edge(vec2(1.0)); }}"),
+            ),
+        ]);
+
+        assert_eq!(functions.matches("fn edge").count(), 1);
+        assert!(functions.contains("struct A { x: f32 }"));
+        assert!(functions.contains("fn b() -> f32"));
+    }
+
+    #[test]
+    #[should_panic(expected = "A and B both define `edge` differently")]
+    fn different_definitions_with_the_same_name_cannot_be_combined() {
+        unique_definitions(&[
+            ("A", HELPER.to_string()),
+            (
+                "B",
+                "fn edge(pos: vec2<f32>) -> f32 { return length(pos); }".to_string(),
+            ),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not one of the combined shaders")]
+    fn plugin_panics_for_shader_not_in_set() {
+        App::new().add_plugins(ExtractToShaderPlugin::<
+            CombinedExtraction<CircleShader, Shaders>,
+        >::default());
+    }
+}
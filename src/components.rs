@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
 
 use crate::prelude::ExtractToShader;
 
@@ -42,3 +42,74 @@ impl<Extract: ExtractToShader> Default for ShaderUsage<Extract> {
     }
 }
 
+/// Sets the draw order of a shape, instead of using the z of its transform.
+///
+/// `layer` is used in place of z when sorting against sprites and shapes without a `ShaderZIndex`.
+/// Shapes in the same layer are drawn in increasing `order`, with ties drawn in a consistent order each frame.
+///
+/// With [`ParamShaderSortMode::Y`] only `order` is used, to order shapes with the same y.
+/// With [`ParamShaderSortMode::ZThenY`] only `layer` is used.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct ShaderZIndex {
+    pub layer: i32,
+    pub order: f32,
+}
+
+impl ShaderZIndex {
+    pub const fn new(layer: i32, order: f32) -> Self {
+        Self { layer, order }
+    }
+}
+
+/// How shapes are sorted against each other and against sprites.
+///
+/// Insert this as a resource to set the mode for every camera, or add it to a camera to override the resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Component, Resource, Reflect)]
+#[reflect(Component, Resource)]
+pub enum ParamShaderSortMode {
+    /// Shapes with a greater z are drawn in front
+    #[default]
+    Z,
+    /// Shapes with a lower y are drawn in front, as in top-down and isometric scenes.
+    ///
+    /// Shapes are sorted against sprites as if their z was `-y`, so sprites should use that as their z.
+    Y,
+    /// Shapes with a greater z are drawn in front, and shapes with the same z are sorted by y
+    ZThenY,
+}
+
+/// Offsets the y used to sort this shape when sorting by y.
+///
+/// For example, a tall shape can be sorted by its base rather than its center.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct ShaderYSortOffset(pub f32);
+
+/// Whether shapes may be drawn in a different order to their sort order, so that they can be drawn in fewer batches.
+///
+/// Shapes are only moved when they do not overlap anything they are moved past, so the image is unchanged.
+/// Other phase items, such as sprites, are never moved past.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub enum ShapeBatchReordering {
+    /// Shapes are always drawn in sort order
+    #[default]
+    Disabled,
+    /// Shapes are moved back to join an earlier batch when they do not overlap anything in between
+    NonOverlapping,
+}
+
+/// Where shapes outside of a camera's view are culled.
+///
+/// With many shapes, most of which are off screen, culling on the GPU avoids checking every shape's bounds on the CPU each frame.
+/// Shapes cached to a texture are always culled on the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub enum ShapeCulling {
+    /// Shapes are culled on the CPU as part of bevy's visibility checks
+    #[default]
+    Cpu,
+    /// Every shape is queued and a compute shader writes the shapes in view of each camera to an indirect draw
+    Gpu,
+}
@@ -0,0 +1,123 @@
+use crate::{shader_code::ShaderCode, vertex_shader::format_instance_reading};
+
+/// The number of instances each workgroup of the cull shader tests at once
+pub(crate) const CULL_WORKGROUP_SIZE: u32 = 256;
+
+/// Creates a compute shader which copies the slots of the instances of each batch which are in view into the culled buffer,
+/// keeping their order, and writes the indirect draw args for each batch
+pub(crate) fn create_cull_shader(code: &ShaderCode) -> bevy::render::render_resource::Shader {
+    let instance_reading = format_instance_reading(code);
+    let frame_expression = &code.frame_expression;
+
+    let source = format!(
+        r##"
+// The instance data, laid out as in `ShapeVertex`
+@group(0) @binding(0)
+var<storage, read> instances: array<u32>;
+// The slots of the instances in sorted order
+@group(0) @binding(1)
+var<storage, read> indices: array<u32>;
+// The slots of the instances which are in view
+@group(0) @binding(2)
+var<storage, read_write> culled: array<u32>;
+
+struct DrawArgs {{
+vertex_count: u32,
+instance_count: u32,
+first_vertex: u32,
+first_instance: u32,
+}};
+@group(0) @binding(3)
+var<storage, read_write> draw_args: array<DrawArgs>;
+
+struct CullBatch {{
+view: u32,
+input_start: u32,
+count: u32,
+output_start: u32,
+}};
+@group(0) @binding(4)
+var<storage, read> batches: array<CullBatch>;
+@group(0) @binding(5)
+var<storage, read> views: array<mat4x4<f32>>;
+
+{instance_reading}
+
+fn is_in_view(vertex: Instance, clip_from_world: mat4x4<f32>) -> bool {{
+var frame = {frame_expression};
+
+// the bounds of the frame after it is rotated, as in the vertex shader
+let c = abs(vertex.rotation.x);
+let s = abs(vertex.rotation.y);
+let half_size = vec2<f32>(c * frame.x + s * frame.y, s * frame.x + c * frame.y) * vertex.scale;
+
+var min_ndc = vec2<f32>(1e30);
+var max_ndc = vec2<f32>(-1e30);
+for (var i = 0u; i < 4u; i++) {{
+    let corner = vec2<f32>(select(-1., 1., (i & 1u) == 0u), select(-1., 1., (i & 2u) == 0u));
+    let pos = vec3<f32>(vertex.position.xy + corner * half_size, vertex.position.z * 2.);
+    let clip = clip_from_world * vec4<f32>(pos, 1.);
+    let ndc = clip.xy / clip.w;
+    min_ndc = min(min_ndc, ndc);
+    max_ndc = max(max_ndc, ndc);
+}}
+
+return all(max_ndc >= vec2<f32>(-1.)) && all(min_ndc <= vec2<f32>(1.));
+}}
+
+var<workgroup> offsets: array<u32, {CULL_WORKGROUP_SIZE}>;
+
+@compute @workgroup_size({CULL_WORKGROUP_SIZE})
+fn cull(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+    @builtin(local_invocation_index) thread: u32
+) {{
+let batch_index = workgroup_id.y * num_workgroups.x + workgroup_id.x;
+if batch_index >= arrayLength(&batches) {{
+    return;
+}}
+let batch = batches[batch_index];
+let clip_from_world = views[batch.view];
+
+var written = 0u;
+for (var chunk = 0u; chunk < batch.count; chunk += {CULL_WORKGROUP_SIZE}u) {{
+    let index = chunk + thread;
+    var visible = 0u;
+    var slot = 0u;
+    if index < batch.count {{
+        slot = indices[batch.input_start + index];
+        visible = select(0u, 1u, is_in_view(read_instance(slot), clip_from_world));
+    }}
+
+    // an inclusive prefix sum gives each visible instance its position in the output
+    offsets[thread] = visible;
+    workgroupBarrier();
+    for (var stride = 1u; stride < {CULL_WORKGROUP_SIZE}u; stride *= 2u) {{
+        var sum = offsets[thread];
+        if thread >= stride {{
+            sum += offsets[thread - stride];
+        }}
+        workgroupBarrier();
+        offsets[thread] = sum;
+        workgroupBarrier();
+    }}
+
+    if visible == 1u {{
+        culled[batch.output_start + written + offsets[thread] - 1u] = slot;
+    }}
+    written += offsets[{CULL_WORKGROUP_SIZE}u - 1u];
+    workgroupBarrier();
+}}
+
+if thread == 0u {{
+    draw_args[batch_index] = DrawArgs(4u, written, 0u, 0u);
+}}
+}}
+"##
+    );
+
+    let tp = &code.type_path;
+
+    bevy::render::render_resource::Shader::from_wgsl(source, format!("cull_{tp}"))
+}
@@ -0,0 +1,356 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraphContext, RenderLabel},
+        render_phase::{DrawFunctions, PhaseItem, ViewSortedRenderPhases},
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntry, BindingType,
+            Buffer, BufferBindingType, BufferDescriptor, BufferUsages, CachedComputePipelineId,
+            CachedPipelineState, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor,
+            Pipeline, PipelineCache, RawBufferVec, ShaderStages,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::ExtractedView,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    components::ShapeCulling, parameterized_shader::*, DrawShaderShape, ExtractedShapes,
+    ViewShapeBatches,
+};
+
+/// The compute pipeline which culls the instances of a shader
+#[derive(Resource)]
+pub(crate) struct ShapeCullPipeline<Shader: ParameterizedShader> {
+    pub layout: BindGroupLayout,
+    pub pipeline: CachedComputePipelineId,
+    phantom: PhantomData<Shader>,
+}
+
+impl<Shader: ParameterizedShader> FromWorld for ShapeCullPipeline<Shader> {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        const fn storage(binding: u32, read_only: bool) -> BindGroupLayoutEntry {
+            BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+        }
+
+        const ENTRIES: &[BindGroupLayoutEntry] = &[
+            storage(0, true),
+            storage(1, true),
+            storage(2, false),
+            storage(3, false),
+            storage(4, true),
+            storage(5, true),
+        ];
+
+        let layout = render_device.create_bind_group_layout("shape_cull_layout", ENTRIES);
+
+        let pipeline =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("shape_cull_pipeline".into()),
+                    layout: vec![layout.clone()],
+                    push_constant_ranges: Vec::new(),
+                    shader: crate::shader_loading::get_cull_handle::<Shader>(),
+                    shader_defs: Vec::new(),
+                    entry_point: "cull".into(),
+                });
+
+        Self {
+            layout,
+            pipeline,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// The compute pipeline, if it has been compiled.
+/// Pipelines queued this frame are not added to the cache until the end of the frame, so `PipelineCache::get_compute_pipeline` would panic
+fn get_compute_pipeline(
+    pipeline_cache: &PipelineCache,
+    pipeline: CachedComputePipelineId,
+) -> Option<&ComputePipeline> {
+    match &pipeline_cache.pipelines().nth(pipeline.id())?.state {
+        CachedPipelineState::Ok(Pipeline::ComputePipeline(pipeline)) => Some(pipeline),
+        _ => None,
+    }
+}
+
+/// A dispatch of the cull shader of one shader, recorded by [`ShapeCullNode`]
+struct CullDispatch {
+    pipeline: CachedComputePipelineId,
+    bind_group: BindGroup,
+    batch_count: u32,
+}
+
+/// The cull shader dispatches of every shader this frame
+#[derive(Resource, Default)]
+pub(crate) struct CullDispatches(Vec<CullDispatch>);
+
+pub(crate) fn clear_cull_dispatches(mut dispatches: ResMut<CullDispatches>) {
+    dispatches.0.clear();
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct ShapeCullLabel;
+
+/// Runs the cull shaders before any camera is rendered, so the culled buffers are ready when the batches are drawn
+#[derive(Default)]
+pub(crate) struct ShapeCullNode;
+
+impl Node for ShapeCullNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let dispatches = &world.resource::<CullDispatches>().0;
+        if dispatches.is_empty() {
+            return Ok(());
+        }
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("shape_cull_pass"),
+                    timestamp_writes: None,
+                });
+
+        for dispatch in dispatches {
+            let Some(pipeline) = get_compute_pipeline(pipeline_cache, dispatch.pipeline) else {
+                continue;
+            };
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &dispatch.bind_group, &[]);
+            // each workgroup culls one batch
+            pass.dispatch_workgroups(
+                dispatch.batch_count.min(MAX_WORKGROUPS),
+                dispatch.batch_count.div_ceil(MAX_WORKGROUPS),
+                1,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A range of instances to cull for a view, as read by the cull shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CullBatch {
+    view: u32,
+    input_start: u32,
+    count: u32,
+    output_start: u32,
+}
+
+/// The size of `DrawIndirectArgs`
+const DRAW_ARGS_SIZE: u64 = 16;
+
+/// The maximum number of workgroups in one dimension of a dispatch
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// The buffers written by the cull shader
+pub(crate) struct CullBuffers {
+    batches: RawBufferVec<CullBatch>,
+    views: RawBufferVec<Mat4>,
+    /// The slots of the instances in view, in sorted order for each batch
+    culled: Option<Buffer>,
+    draw_args: Option<Buffer>,
+}
+
+impl Default for CullBuffers {
+    fn default() -> Self {
+        Self {
+            batches: RawBufferVec::new(BufferUsages::STORAGE),
+            views: RawBufferVec::new(BufferUsages::STORAGE),
+            culled: None,
+            draw_args: None,
+        }
+    }
+}
+
+impl CullBuffers {
+    /// The instance slots and the indirect draw args for a culled batch
+    pub fn get(&self, index: u32) -> Option<(&Buffer, u64, &Buffer, u64)> {
+        let batch = self.batches.values().get(index as usize)?;
+        let culled = self.culled.as_ref()?;
+        let draw_args = self.draw_args.as_ref()?;
+
+        Some((
+            culled,
+            batch.output_start as u64 * 4,
+            draw_args,
+            index as u64 * DRAW_ARGS_SIZE,
+        ))
+    }
+
+    pub fn clear(&mut self) {
+        self.batches.clear();
+        self.views.clear();
+    }
+
+    /// Makes sure `buffer` can hold at least `size` bytes
+    fn reserve(
+        buffer: &mut Option<Buffer>,
+        size: u64,
+        usage: BufferUsages,
+        label: &'static str,
+        render_device: &RenderDevice,
+    ) {
+        if buffer.as_ref().is_some_and(|buffer| buffer.size() >= size) {
+            return;
+        }
+
+        *buffer = Some(render_device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: size.next_power_of_two(),
+            usage,
+            mapped_at_creation: false,
+        }));
+    }
+}
+
+/// Prepares the buffers to cull the instances of each batch of this shader in each view on the GPU.
+///
+/// The cull shader is dispatched by [`ShapeCullNode`] and the batches are then drawn with `draw_indirect`.
+/// Until the pipeline is compiled, batches are drawn without culling.
+pub(crate) fn cull_shapes<Shader: ParameterizedShader>(
+    culling: Res<ShapeCulling>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<ShapeCullPipeline<Shader>>,
+    pipeline_cache: Res<PipelineCache>,
+    mut dispatches: ResMut<CullDispatches>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    transparent_render_phases: Res<ViewSortedRenderPhases<Transparent2d>>,
+    mut view_batches: ResMut<ViewShapeBatches>,
+    mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
+    views: Query<&ExtractedView>,
+) {
+    let extracted_shapes = extracted_shapes.as_mut();
+    let cull_buffers = &mut extracted_shapes.cull_buffers;
+    cull_buffers.clear();
+
+    if *culling != ShapeCulling::Gpu {
+        return;
+    }
+    if get_compute_pipeline(&pipeline_cache, pipeline.pipeline).is_none() {
+        return;
+    }
+    let (Some(instances), Some(indices)) = (
+        extracted_shapes.instance_buffer.buffer(),
+        extracted_shapes.indices.buffer(),
+    ) else {
+        return;
+    };
+    let Some(draw_function) = draw_functions.read().get_id::<DrawShaderShape<Shader>>() else {
+        return;
+    };
+
+    let mut output_len = 0;
+
+    for (view_entity, transparent_phase) in transparent_render_phases.iter() {
+        let Ok(view) = views.get(*view_entity) else {
+            continue;
+        };
+        let view_index = cull_buffers
+            .views
+            .push(view.clip_from_world.unwrap_or_else(|| {
+                view.clip_from_view * view.world_from_view.compute_matrix().inverse()
+            })) as u32;
+
+        // items joined with an earlier item are drawn by that item
+        let mut index = 0;
+        while let Some(item) = transparent_phase.items.get(index) {
+            index += item.batch_range().len().max(1);
+            if item.draw_function != draw_function {
+                continue;
+            }
+            let Some(batch) = view_batches.get_mut(*view_entity, item.extra_index()) else {
+                continue;
+            };
+
+            let count = batch.range.len() as u32;
+            batch.culled = Some(cull_buffers.batches.push(CullBatch {
+                view: view_index,
+                input_start: batch.range.start,
+                count,
+                output_start: output_len,
+            }) as u32);
+            output_len += count;
+        }
+    }
+
+    let batch_count = cull_buffers.batches.len() as u32;
+    if batch_count == 0 {
+        return;
+    }
+
+    cull_buffers
+        .batches
+        .write_buffer(&render_device, &render_queue);
+    cull_buffers
+        .views
+        .write_buffer(&render_device, &render_queue);
+    CullBuffers::reserve(
+        &mut cull_buffers.culled,
+        output_len.max(1) as u64 * 4,
+        BufferUsages::STORAGE | BufferUsages::VERTEX,
+        "shape_culled_buffer",
+        &render_device,
+    );
+    CullBuffers::reserve(
+        &mut cull_buffers.draw_args,
+        batch_count as u64 * DRAW_ARGS_SIZE,
+        BufferUsages::STORAGE | BufferUsages::INDIRECT,
+        "shape_draw_args_buffer",
+        &render_device,
+    );
+
+    let (Some(culled), Some(draw_args), Some(batches), Some(views)) = (
+        cull_buffers.culled.as_ref(),
+        cull_buffers.draw_args.as_ref(),
+        cull_buffers.batches.buffer(),
+        cull_buffers.views.buffer(),
+    ) else {
+        return;
+    };
+
+    let bind_group = render_device.create_bind_group(
+        "shape_cull_bind_group",
+        &pipeline.layout,
+        &BindGroupEntries::sequential((
+            instances.as_entire_binding(),
+            indices.as_entire_binding(),
+            culled.as_entire_binding(),
+            draw_args.as_entire_binding(),
+            batches.as_entire_binding(),
+            views.as_entire_binding(),
+        )),
+    );
+
+    dispatches.0.push(CullDispatch {
+        pipeline: pipeline.pipeline,
+        bind_group,
+        batch_count,
+    });
+}
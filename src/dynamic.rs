@@ -0,0 +1,699 @@
+use bevy::{
+    asset::{
+        io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState, RecursiveDependencyLoadState,
+    },
+    color::LinearRgba,
+    ecs::{query::WorldQuery, system::SystemParam},
+    math::{IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Vec4},
+    prelude::*,
+    reflect::DynamicStruct,
+    render::view::VisibilitySystems,
+};
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+
+use crate::{
+    bundle::ShaderCheckVisibility,
+    cull_shader, fragment_shader,
+    frame::Frame,
+    parameterized_shader::{ExtractToShader, FragmentImport, ParameterizedShader},
+    registry::{ParamShaderRegistry, ParamShaderRegistryPlugin},
+    shader_code::ShaderCode,
+    shader_loading::{get_cull_asset_id, get_fragment_asset_id, get_vertex_asset_id},
+    shader_params::ShaderParams,
+    shader_uniforms::NoUniforms,
+    vertex_shader, ShaderUsage, ShapeVertex,
+};
+
+/// The number of [`DynamicShaderDefinition`]s which can be drawn at once
+pub const MAX_DYNAMIC_SHADERS: usize = 8;
+
+/// The number of words the params of a [`DynamicShaderDefinition`] can use, enough for four `vec4<f32>`s
+pub const MAX_DYNAMIC_PARAM_WORDS: usize = 16;
+
+/// A shader defined by a `.pshader.ron` file, so that it can be written without Rust.
+///
+/// The file declares the params of the shader, its imports, its frame and its fragment body,
+/// which are used as in a [`ParameterizedShader`].
+/// Shapes are drawn with it by giving them [`DynamicShaderParams`],
+/// and changes to the file are hot reloaded when the `file_watcher` feature is enabled.
+///
+/// ```ron
+/// (
+///     params: [
+///         (name: "color", type: LinearRgba),
+///         (name: "radius", type: F32),
+///     ],
+///     imports: [(path: "simple.wgsl", import_path: "fill::simple")],
+///     frame: (1.0, 1.0),
+///     fragment_body: "
+///         let d = length(in.pos) - in.radius;
+///         return fill::simple::fill(d, in.color, in.pos);
+///     ",
+/// )
+/// ```
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct DynamicShaderDefinition {
+    /// The params of each shape, in the order they are laid out
+    pub params: Vec<DynamicParam>,
+    #[serde(default)]
+    pub imports: Vec<DynamicImport>,
+    /// The half-width and half-height of the frame, used to calculate the bounds of each shape
+    #[serde(default = "default_frame")]
+    pub frame: (f32, f32),
+    /// An expression that returns a `vec2<f32>` representing the half-width and half-height of the frame,
+    /// which should be no bigger than `frame`. By default this is `frame`
+    #[serde(default)]
+    pub frame_expression: Option<String>,
+    /// The body of the fragment function, see [`ParameterizedShader::fragment_body`]
+    pub fragment_body: String,
+    /// Functions and types which can be used by `frame_expression`
+    #[serde(default)]
+    pub vertex_functions: String,
+    /// Functions and types which can be used by `fragment_body`
+    #[serde(default)]
+    pub fragment_functions: String,
+    /// Keeps the imports loaded
+    #[serde(skip)]
+    #[dependency]
+    import_handles: Vec<Handle<Shader>>,
+}
+
+fn default_frame() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+impl DynamicShaderDefinition {
+    /// The number of words used by the params
+    pub fn param_words(&self) -> usize {
+        self.params
+            .iter()
+            .map(|param| param.param_type.word_count())
+            .sum()
+    }
+
+    fn frame(&self) -> Frame {
+        Frame {
+            half_width: self.frame.0,
+            half_height: self.frame.1,
+        }
+    }
+
+    fn code<Shader: ParameterizedShader>(&self) -> ShaderCode {
+        let mut params = DynamicStruct::default();
+        for param in self.params.iter() {
+            params.insert_boxed(&param.name, param.param_type.default_value());
+        }
+
+        ShaderCode {
+            params: Box::new(params),
+            stride: (std::mem::size_of::<ShapeVertex<DynamicParams>>() / 4) as u32,
+            fragment_body: self.fragment_body.clone(),
+            frame_expression: self
+                .frame_expression
+                .clone()
+                .unwrap_or_else(|| self.frame().into()),
+            imports: self
+                .imports
+                .iter()
+                .map(|import| import.import_path.clone())
+                .collect(),
+            vertex_functions: self.vertex_functions.clone(),
+            fragment_functions: self.fragment_functions.clone(),
+            ..ShaderCode::of::<Shader>()
+        }
+    }
+}
+
+/// A param of a [`DynamicShaderDefinition`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DynamicParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: DynamicParamType,
+}
+
+/// The types which params of a [`DynamicShaderDefinition`] can have.
+/// The values of the params must be given as the matching Rust type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DynamicParamType {
+    F32,
+    U32,
+    I32,
+    Vec2,
+    Vec3,
+    Vec4,
+    UVec2,
+    UVec3,
+    UVec4,
+    IVec2,
+    IVec3,
+    IVec4,
+    LinearRgba,
+}
+
+impl DynamicParamType {
+    /// The number of words a param of this type uses
+    pub fn word_count(self) -> usize {
+        match self {
+            Self::F32 | Self::U32 | Self::I32 => 1,
+            Self::Vec2 | Self::UVec2 | Self::IVec2 => 2,
+            Self::Vec3 | Self::UVec3 | Self::IVec3 => 3,
+            Self::Vec4 | Self::UVec4 | Self::IVec4 | Self::LinearRgba => 4,
+        }
+    }
+
+    fn default_value(self) -> Box<dyn Reflect> {
+        match self {
+            Self::F32 => Box::new(0.0f32),
+            Self::U32 => Box::new(0u32),
+            Self::I32 => Box::new(0i32),
+            Self::Vec2 => Box::new(Vec2::ZERO),
+            Self::Vec3 => Box::new(Vec3::ZERO),
+            Self::Vec4 => Box::new(Vec4::ZERO),
+            Self::UVec2 => Box::new(UVec2::ZERO),
+            Self::UVec3 => Box::new(UVec3::ZERO),
+            Self::UVec4 => Box::new(UVec4::ZERO),
+            Self::IVec2 => Box::new(IVec2::ZERO),
+            Self::IVec3 => Box::new(IVec3::ZERO),
+            Self::IVec4 => Box::new(IVec4::ZERO),
+            Self::LinearRgba => Box::new(LinearRgba::NONE),
+        }
+    }
+
+    /// Writes the value into the words, which are left as zero if it is not of this type
+    fn write(self, value: &dyn Reflect, words: &mut [u32]) {
+        match self {
+            Self::F32 => write_words::<f32>(value, words),
+            Self::U32 => write_words::<u32>(value, words),
+            Self::I32 => write_words::<i32>(value, words),
+            Self::Vec2 => write_words::<Vec2>(value, words),
+            Self::Vec3 => write_words::<Vec3>(value, words),
+            Self::Vec4 => write_words::<Vec4>(value, words),
+            Self::UVec2 => write_words::<UVec2>(value, words),
+            Self::UVec3 => write_words::<UVec3>(value, words),
+            Self::UVec4 => write_words::<UVec4>(value, words),
+            Self::IVec2 => write_words::<IVec2>(value, words),
+            Self::IVec3 => write_words::<IVec3>(value, words),
+            Self::IVec4 => write_words::<IVec4>(value, words),
+            Self::LinearRgba => write_words::<LinearRgba>(value, words),
+        }
+    }
+}
+
+fn write_words<T: Reflect + Pod>(value: &dyn Reflect, words: &mut [u32]) {
+    if let Some(value) = value.downcast_ref::<T>() {
+        words.copy_from_slice(bytemuck::cast_slice(std::slice::from_ref(value)));
+    }
+}
+
+/// A file imported by a [`DynamicShaderDefinition`], see [`FragmentImport`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DynamicImport {
+    pub path: String,
+    pub import_path: String,
+}
+
+impl From<FragmentImport> for DynamicImport {
+    fn from(value: FragmentImport) -> Self {
+        Self {
+            path: value.path.to_string(),
+            import_path: value.import_path.to_string(),
+        }
+    }
+}
+
+/// The params of a shape drawn with a [`DynamicShaderDefinition`].
+///
+/// Params are matched to those of the definition by name.
+/// Params which are missing, or whose values are not of the declared type, are zero.
+#[derive(Component, Debug, Default)]
+pub struct DynamicShaderParams {
+    pub definition: Handle<DynamicShaderDefinition>,
+    pub params: DynamicStruct,
+}
+
+impl DynamicShaderParams {
+    pub fn new(definition: Handle<DynamicShaderDefinition>) -> Self {
+        Self {
+            definition,
+            params: Default::default(),
+        }
+    }
+
+    /// Sets a param
+    pub fn with(mut self, name: &str, value: impl Reflect) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Sets a param
+    pub fn set(&mut self, name: &str, value: impl Reflect) {
+        self.params.insert(name, value);
+    }
+}
+
+impl Clone for DynamicShaderParams {
+    fn clone(&self) -> Self {
+        Self {
+            definition: self.definition.clone(),
+            params: self.params.clone_dynamic(),
+        }
+    }
+}
+
+#[derive(Bundle)]
+pub struct DynamicShaderBundle {
+    pub params: DynamicShaderParams,
+    /// A transform, set this to set the position, orientation and scale of the shape
+    pub transform: Transform,
+    /// Indicates that the shader visibility should be checked
+    pub shader_check_visibility: ShaderCheckVisibility,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+}
+
+impl Default for DynamicShaderBundle {
+    fn default() -> Self {
+        Self {
+            params: Default::default(),
+            transform: Default::default(),
+            shader_check_visibility: ShaderCheckVisibility,
+            global_transform: Default::default(),
+            visibility: Default::default(),
+            inherited_visibility: Default::default(),
+            view_visibility: Default::default(),
+        }
+    }
+}
+
+/// One of the shaders which [`DynamicShaderDefinition`]s are drawn with.
+/// Each loaded definition is given its own, and its vertex, fragment and cull shaders are generated from the definition
+#[derive(TypePath)]
+pub struct DynamicShader<const SLOT: usize>;
+
+/// The params of every [`DynamicShader`], as laid out by its definition
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, Pod, Zeroable)]
+pub struct DynamicParams {
+    #[reflect(ignore)]
+    words: [u32; MAX_DYNAMIC_PARAM_WORDS],
+    /// Not read by the shaders, which only read the words used by the definition
+    #[reflect(ignore)]
+    frame: [f32; 2],
+}
+
+impl ShaderParams for DynamicParams {}
+
+impl<const SLOT: usize> ParameterizedShader for DynamicShader<SLOT> {
+    type Params = DynamicParams;
+    type Uniforms = NoUniforms;
+
+    fn fragment_body() -> impl Into<String> {
+        // replaced by the definition using this shader
+        "return vec4<f32>(0.0);"
+    }
+
+    fn frame_expression() -> impl Into<String> {
+        Frame::default()
+    }
+
+    fn frame(params: &Self::Params) -> Frame {
+        let [half_width, half_height] = params.frame;
+        Frame {
+            half_width,
+            half_height,
+        }
+    }
+
+    fn imports() -> impl Iterator<Item = FragmentImport> {
+        std::iter::empty()
+    }
+
+    const USE_TIME: bool = true;
+
+    const UUID: u128 = 0x5c1e8a4f0d2b4e6f9a3c7b1d2e4f6a80 + SLOT as u128;
+}
+
+impl<const SLOT: usize> ExtractToShader for DynamicShader<SLOT> {
+    type Shader = Self;
+    type ParamsQuery<'a> = &'a DynamicShaderParams;
+    type ParamsBundle = DynamicShaderParams;
+    type ResourceParams<'w> = Res<'w, DynamicShaders>;
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+    ) -> DynamicParams {
+        resource.params(SLOT, &query_item.params)
+    }
+}
+
+/// The definitions which are drawn with each [`DynamicShader`]
+#[derive(Resource, Default)]
+pub struct DynamicShaders {
+    slots: [Option<DynamicShaderSlot>; MAX_DYNAMIC_SHADERS],
+}
+
+struct DynamicShaderSlot {
+    definition: AssetId<DynamicShaderDefinition>,
+    params: Vec<DynamicParam>,
+    frame: Frame,
+}
+
+impl DynamicShaders {
+    /// The slot of the [`DynamicShader`] this definition is drawn with, if it has loaded
+    pub fn slot_of(&self, definition: AssetId<DynamicShaderDefinition>) -> Option<usize> {
+        self.slots.iter().position(|slot| {
+            slot.as_ref()
+                .is_some_and(|slot| slot.definition == definition)
+        })
+    }
+
+    fn params(&self, slot: usize, values: &DynamicStruct) -> DynamicParams {
+        let mut params = DynamicParams::default();
+        let Some(slot) = &self.slots[slot] else {
+            return params;
+        };
+
+        params.frame = [slot.frame.half_width, slot.frame.half_height];
+
+        let mut word = 0;
+        for param in slot.params.iter() {
+            let word_count = param.param_type.word_count();
+            if let Some(value) = values.field(&param.name) {
+                param
+                    .param_type
+                    .write(value, &mut params.words[word..(word + word_count)]);
+            }
+            word += word_count;
+        }
+
+        params
+    }
+}
+
+/// The functions of each slot, which is a different type
+struct SlotFunctions {
+    register: fn(&mut ParamShaderRegistry),
+    unregister: fn(&mut ParamShaderRegistry),
+    insert_usage: fn(&mut Commands, Entity),
+    remove_usage: fn(&mut Commands, Entity),
+    generate_shaders: fn(&DynamicShaderDefinition, &mut Assets<Shader>),
+}
+
+const fn slot_functions<const SLOT: usize>() -> SlotFunctions {
+    SlotFunctions {
+        register: |registry| registry.register::<DynamicShader<SLOT>>(),
+        unregister: |registry| registry.unregister::<DynamicShader<SLOT>>(),
+        insert_usage: |commands, entity| {
+            commands
+                .entity(entity)
+                .try_insert(ShaderUsage::<DynamicShader<SLOT>>::default());
+        },
+        remove_usage: |commands, entity| {
+            commands
+                .entity(entity)
+                .remove::<ShaderUsage<DynamicShader<SLOT>>>();
+        },
+        generate_shaders: generate_shaders::<SLOT>,
+    }
+}
+
+const SLOTS: [SlotFunctions; MAX_DYNAMIC_SHADERS] = [
+    slot_functions::<0>(),
+    slot_functions::<1>(),
+    slot_functions::<2>(),
+    slot_functions::<3>(),
+    slot_functions::<4>(),
+    slot_functions::<5>(),
+    slot_functions::<6>(),
+    slot_functions::<7>(),
+];
+
+/// Replaces the generated shaders of a slot, which are then recompiled
+fn generate_shaders<const SLOT: usize>(
+    definition: &DynamicShaderDefinition,
+    shaders: &mut Assets<Shader>,
+) {
+    let code = definition.code::<DynamicShader<SLOT>>();
+
+    shaders.insert(
+        get_vertex_asset_id::<DynamicShader<SLOT>>(),
+        vertex_shader::create_vertex_shader(&code),
+    );
+    shaders.insert(
+        get_fragment_asset_id::<DynamicShader<SLOT>>(),
+        fragment_shader::create_fragment_shader(&code),
+    );
+    shaders.insert(
+        get_cull_asset_id::<DynamicShader<SLOT>>(),
+        cull_shader::create_cull_shader(&code),
+    );
+}
+
+/// Gives each loaded definition a slot, and regenerates its shaders when it changes.
+/// Definitions whose imports fail to load are never given a slot, which is logged so that their shapes are not silently missing
+fn update_dynamic_shaders(
+    mut events: EventReader<AssetEvent<DynamicShaderDefinition>>,
+    definitions: Res<Assets<DynamicShaderDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut loading: Local<Vec<AssetId<DynamicShaderDefinition>>>,
+    mut dynamic_shaders: ResMut<DynamicShaders>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut registry: ResMut<ParamShaderRegistry>,
+) {
+    for event in events.read() {
+        match *event {
+            AssetEvent::Added { id } => loading.push(id),
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+                loading.retain(|loading| *loading != id);
+
+                let Some(definition) = definitions.get(id) else {
+                    continue;
+                };
+
+                let Some(slot) = dynamic_shaders
+                    .slot_of(id)
+                    .or_else(|| dynamic_shaders.slots.iter().position(Option::is_none))
+                else {
+                    error!("Dynamic shader {id} cannot be drawn, as only {MAX_DYNAMIC_SHADERS} can be drawn at once");
+                    continue;
+                };
+
+                (SLOTS[slot].generate_shaders)(definition, &mut shaders);
+                (SLOTS[slot].register)(&mut registry);
+
+                dynamic_shaders.slots[slot] = Some(DynamicShaderSlot {
+                    definition: id,
+                    params: definition.params.clone(),
+                    frame: definition.frame(),
+                });
+            }
+            AssetEvent::Removed { id } => {
+                loading.retain(|loading| *loading != id);
+
+                if let Some(slot) = dynamic_shaders.slot_of(id) {
+                    (SLOTS[slot].unregister)(&mut registry);
+                    dynamic_shaders.slots[slot] = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    loading.retain(|&id| {
+        match asset_server.recursive_dependency_load_state(id) {
+            RecursiveDependencyLoadState::Loading => return true,
+            RecursiveDependencyLoadState::Failed => {}
+            _ => return false,
+        }
+
+        let failed_imports = definitions
+            .get(id)
+            .into_iter()
+            .flat_map(|definition| definition.import_handles.iter())
+            .filter(|import| matches!(asset_server.load_state(import.id()), LoadState::Failed(_)))
+            .filter_map(|import| import.path().map(ToString::to_string))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let path = asset_server
+            .get_path(id)
+            .map_or_else(|| id.to_string(), |path| path.to_string());
+        warn!("Dynamic shader {path} cannot be drawn, as its imports failed to load: {failed_imports}");
+        false
+    });
+}
+
+/// The slot of the [`DynamicShader`] an entity is drawn with
+#[derive(Component)]
+struct DynamicShaderUsage(usize);
+
+/// Draws each entity with the [`DynamicShader`] of its definition
+fn update_dynamic_shader_usages(
+    mut commands: Commands,
+    dynamic_shaders: Res<DynamicShaders>,
+    shapes: Query<(Entity, &DynamicShaderParams, Option<&DynamicShaderUsage>)>,
+) {
+    for (entity, params, usage) in shapes.iter() {
+        let slot = dynamic_shaders.slot_of(params.definition.id());
+        let current = usage.map(|usage| usage.0);
+        if slot == current {
+            continue;
+        }
+
+        if let Some(current) = current {
+            (SLOTS[current].remove_usage)(&mut commands, entity);
+            commands.entity(entity).remove::<DynamicShaderUsage>();
+        }
+
+        if let Some(slot) = slot {
+            (SLOTS[slot].insert_usage)(&mut commands, entity);
+            commands.entity(entity).try_insert(DynamicShaderUsage(slot));
+        }
+    }
+}
+
+/// Draws shapes with [`DynamicShaderParams`] using the [`DynamicShaderDefinition`]s loaded from `.pshader.ron` files
+#[derive(Debug, Default)]
+pub struct DynamicShaderPlugin;
+
+impl Plugin for DynamicShaderPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<ParamShaderRegistryPlugin>() {
+            app.add_plugins(ParamShaderRegistryPlugin);
+        }
+
+        app.init_asset::<DynamicShaderDefinition>()
+            .init_asset_loader::<DynamicShaderLoader>()
+            .init_resource::<DynamicShaders>()
+            .add_systems(
+                PostUpdate,
+                (update_dynamic_shaders, update_dynamic_shader_usages)
+                    .chain()
+                    .before(VisibilitySystems::CalculateBounds),
+            );
+    }
+}
+
+/// Loads a [`DynamicShaderDefinition`] from a RON file
+#[derive(Default)]
+struct DynamicShaderLoader;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicShaderLoaderError {
+    #[error("Could not read the shader: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the shader: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("The params use {0} words, but at most {MAX_DYNAMIC_PARAM_WORDS} can be used")]
+    TooManyParams(usize),
+}
+
+impl AssetLoader for DynamicShaderLoader {
+    type Asset = DynamicShaderDefinition;
+    type Settings = ();
+    type Error = DynamicShaderLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let mut definition: DynamicShaderDefinition = ron::de::from_bytes(&bytes)?;
+
+        let param_words = definition.param_words();
+        if param_words > MAX_DYNAMIC_PARAM_WORDS {
+            return Err(DynamicShaderLoaderError::TooManyParams(param_words));
+        }
+
+        definition.import_handles = definition
+            .imports
+            .iter()
+            .map(|import| load_context.load(import.path.clone()))
+            .collect();
+
+        Ok(definition)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pshader.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, param_type: DynamicParamType) -> DynamicParam {
+        DynamicParam {
+            name: name.to_string(),
+            param_type,
+        }
+    }
+
+    fn dynamic_shaders() -> DynamicShaders {
+        let mut dynamic_shaders = DynamicShaders::default();
+        dynamic_shaders.slots[1] = Some(DynamicShaderSlot {
+            definition: AssetId::default(),
+            params: vec![
+                param("radius", DynamicParamType::F32),
+                param("color", DynamicParamType::LinearRgba),
+                param("count", DynamicParamType::U32),
+            ],
+            frame: Frame {
+                half_width: 2.0,
+                half_height: 3.0,
+            },
+        });
+        dynamic_shaders
+    }
+
+    #[test]
+    fn params_are_laid_out_in_order() {
+        let mut values = DynamicStruct::default();
+        values.insert("count", 7u32);
+        values.insert("radius", 0.5f32);
+        values.insert("color", LinearRgba::new(0.1, 0.2, 0.3, 1.0));
+
+        let params = dynamic_shaders().params(1, &values);
+
+        assert_eq!(params.frame, [2.0, 3.0]);
+        assert_eq!(f32::from_bits(params.words[0]), 0.5);
+        assert_eq!(
+            bytemuck::cast_slice::<u32, f32>(&params.words[1..5]),
+            [0.1, 0.2, 0.3, 1.0]
+        );
+        assert_eq!(params.words[5], 7);
+    }
+
+    #[test]
+    fn missing_and_mistyped_params_are_zero() {
+        let mut values = DynamicStruct::default();
+        values.insert("radius", 0.5f64);
+        values.insert("count", 7u32);
+
+        let params = dynamic_shaders().params(1, &values);
+
+        assert_eq!(params.words[..5], [0; 5]);
+        assert_eq!(params.words[5], 7);
+    }
+
+    #[test]
+    fn empty_slots_have_default_params() {
+        let mut values = DynamicStruct::default();
+        values.insert("radius", 0.5f32);
+
+        assert_eq!(
+            dynamic_shaders().params(0, &values),
+            DynamicParams::default()
+        );
+    }
+}
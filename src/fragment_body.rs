@@ -0,0 +1,45 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+
+/// The body of the fragment function of a shader, loaded from a `.body.wgsl` file.
+///
+/// See [`ParameterizedShader::FRAGMENT_BODY_PATH`](crate::parameterized_shader::ParameterizedShader::FRAGMENT_BODY_PATH)
+#[derive(Asset, TypePath, Debug, Clone, PartialEq)]
+pub struct FragmentBody(pub String);
+
+/// Loads a [`FragmentBody`] from the text of a file
+#[derive(Default)]
+pub(crate) struct FragmentBodyLoader;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FragmentBodyLoaderError {
+    #[error("Could not read the fragment body: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The fragment body is not valid UTF-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+impl AssetLoader for FragmentBodyLoader {
+    type Asset = FragmentBody;
+    type Settings = ();
+    type Error = FragmentBodyLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        Ok(FragmentBody(String::from_utf8(bytes)?))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // more specific than the `wgsl` extension of shaders
+        &["body.wgsl"]
+    }
+}
@@ -1,16 +1,19 @@
-use crate::prelude::ParameterizedShader;
+use crate::shader_code::ShaderCode;
 
-pub(crate) fn create_fragment_shader<Shader: ParameterizedShader>() -> bevy::render::render_resource::Shader {
-    let params_locations = crate::helpers::format_params_locations::<Shader::Params>(1);
+pub(crate) fn create_fragment_shader(code: &ShaderCode) -> bevy::render::render_resource::Shader {
+    let params_locations = crate::helpers::format_params_locations(code.params.as_ref(), 1);
 
-    let fragment_body: String = Shader::fragment_body().into();
+    let fragment_body = &code.fragment_body;
+    let functions = &code.fragment_functions;
 
-    let imports = Shader::imports()
-        .map(|x| format!("#import {}", x.import_path))
+    let imports = code
+        .imports
+        .iter()
+        .map(|import_path| format!("#import {import_path}"))
         .collect::<Vec<String>>()
         .join("\n");
 
-    let (time_import, time_group) = if Shader::USE_TIME {
+    let (time_import, time_group) = if code.use_time {
         (
             "#import bevy_render::globals::Globals",
             "@group(0) @binding(1)
@@ -20,6 +23,30 @@ pub(crate) fn create_fragment_shader<Shader: ParameterizedShader>() -> bevy::ren
         ("", "")
     };
 
+    let uniforms_group = if let Some(uniforms) = &code.uniforms {
+        let fields = crate::helpers::format_struct_fields(uniforms.as_ref());
+        format!(
+            "struct Uniforms {{
+{fields}
+}};
+@group(2) @binding(0)
+var<uniform> uniforms: Uniforms;"
+        )
+    } else {
+        "".to_string()
+    };
+
+    let texture_group = if let Some(group) = code.texture_group {
+        format!(
+            "@group({group}) @binding(0)
+var shape_texture: texture_2d<f32>;
+@group({group}) @binding(1)
+var shape_sampler: sampler;"
+        )
+    } else {
+        "".to_string()
+    };
+
     let source = format!(
         r#"
 {time_import}
@@ -27,6 +54,12 @@ pub(crate) fn create_fragment_shader<Shader: ParameterizedShader>() -> bevy::ren
 
 {time_group}
 
+{uniforms_group}
+
+{texture_group}
+
+{functions}
+
 struct FragmentInput {{
 @location(0) pos: vec2<f32>,
 {params_locations}
@@ -42,7 +75,7 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {{
     );
     //bevy::log::info!("{source}");
 
-    let tp = Shader::type_path();
+    let tp = &code.type_path;
 
     bevy::render::render_resource::Shader::from_wgsl(source, format!("fragment_{tp}"))
 }
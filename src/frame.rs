@@ -1,4 +1,3 @@
-
 /// Bounds for describing how far the fragment shader of a shape will reach, should be bigger than the shape unless you want to clip it
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Frame {
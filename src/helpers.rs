@@ -1,13 +1,17 @@
 use std::any::TypeId;
 
-use bevy::{color::LinearRgba, math::Vec4, render::render_resource::VertexFormat};
+use bevy::{
+    color::LinearRgba,
+    math::{IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Vec4},
+    reflect::{Reflect, Struct},
+    render::render_resource::VertexFormat,
+};
 
 use crate::shader_params::*;
 
-pub(crate) fn format_params_locations<PARAMS: ShaderParams>(previous_params: u32) -> String {
+pub(crate) fn format_params_locations(proxy: &dyn Struct, previous_params: u32) -> String {
     let mut result = "".to_string();
 
-    let proxy = PARAMS::default();
     let param_count = proxy.field_len();
 
     let mut loc = previous_params;
@@ -27,7 +31,14 @@ pub(crate) fn format_params_locations<PARAMS: ShaderParams>(previous_params: u32
             panic!("Cannot convert {name} to wgsl type",);
         };
 
-        result.push_str(format!("@location({loc}) {name}: {type_name},\n").as_str());
+        // integers cannot be interpolated
+        let interpolation = if type_name.contains("u32") || type_name.contains("i32") {
+            "@interpolate(flat) "
+        } else {
+            ""
+        };
+
+        result.push_str(format!("@location({loc}) {interpolation}{name}: {type_name},\n").as_str());
         loc += 1;
     }
 
@@ -73,7 +84,95 @@ pub(crate) fn get_vertex_format(type_id: TypeId) -> Option<VertexFormat> {
         Some(VertexFormat::Float32x4)
     } else if type_id == TypeId::of::<LinearRgba>() {
         Some(VertexFormat::Float32x4)
+    } else if type_id == TypeId::of::<UVec2>() {
+        Some(VertexFormat::Uint32x2)
+    } else if type_id == TypeId::of::<UVec3>() {
+        Some(VertexFormat::Uint32x3)
+    } else if type_id == TypeId::of::<UVec4>() {
+        Some(VertexFormat::Uint32x4)
+    } else if type_id == TypeId::of::<IVec2>() {
+        Some(VertexFormat::Sint32x2)
+    } else if type_id == TypeId::of::<IVec3>() {
+        Some(VertexFormat::Sint32x3)
+    } else if type_id == TypeId::of::<IVec4>() {
+        Some(VertexFormat::Sint32x4)
     } else {
         None
     }
 }
+
+/// The vertex format of a params field, panics if the field type is not supported
+fn field_vertex_format(field: &dyn Reflect) -> VertexFormat {
+    let Some(format) = get_vertex_format(field.type_id()) else {
+        let name = field
+            .get_represented_type_info()
+            .map(|info| info.type_path())
+            .unwrap_or_else(|| field.reflect_type_path());
+
+        panic!("Cannot convert {name} to wgsl type",);
+    };
+    format
+}
+
+/// The fields of the params, to be placed in a wgsl struct
+pub(crate) fn format_params_fields<PARAMS: ShaderParams>() -> String {
+    format_struct_fields(&PARAMS::default())
+}
+
+/// The fields of a struct, to be placed in a wgsl struct
+pub(crate) fn format_struct_fields(proxy: &dyn Struct) -> String {
+    let mut result = "".to_string();
+
+    for (index, field) in proxy.iter_fields().enumerate() {
+        let name = proxy.name_at(index).unwrap();
+        field_vertex_format(field);
+        let type_name = get_wgsl_type_name(field.type_id()).unwrap();
+
+        result.push_str(format!("{name}: {type_name},\n").as_str());
+    }
+
+    result
+}
+
+/// Assignments which read each param from an array of words such as the `instances` storage buffer, starting at word `base + first_word`
+pub(crate) fn format_params_reads(
+    proxy: &dyn Struct,
+    variable: &str,
+    array: &str,
+    first_word: u32,
+) -> String {
+    let mut result = "".to_string();
+
+    let mut word = first_word;
+
+    for (index, field) in proxy.iter_fields().enumerate() {
+        let name = proxy.name_at(index).unwrap();
+        let word_count = (field_vertex_format(field).size() / 4) as u32;
+        let type_name = get_wgsl_type_name(field.type_id()).unwrap();
+
+        let read = format_words_read(array, type_name, word, word_count);
+        result.push_str(format!("{variable}.{name} = {read};\n").as_str());
+        word += word_count;
+    }
+
+    result
+}
+
+/// An expression which reads `count` words from an array of words as the given type
+pub(crate) fn format_words_read(
+    array: &str,
+    type_name: &str,
+    first_word: u32,
+    count: u32,
+) -> String {
+    let words = (first_word..(first_word + count))
+        .map(|word| format!("{array}[base + {word}u]"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if count == 1 {
+        format!("bitcast<{type_name}>({words})")
+    } else {
+        format!("bitcast<{type_name}>(vec{count}<u32>({words}))")
+    }
+}
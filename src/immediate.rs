@@ -0,0 +1,202 @@
+use std::any::TypeId;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{view::RenderLayers, Extract},
+};
+
+use crate::{
+    instance_buffer::ShapeKey, parameterized_shader::ParameterizedShader, sorting::ShapeSortInputs,
+    ExtractedShape, ExtractedShapes, ShaderYSortOffset, ShaderZIndex, ShapeVertex,
+};
+
+/// The entity of shapes drawn with [`ParamShapes`], which are not drawn for any entity.
+/// Every view treats it as visible, except snapshots of particular entities
+pub(crate) const IMMEDIATE_SHAPE_ENTITY: Entity = Entity::PLACEHOLDER;
+
+/// Draws shapes for a single frame, without spawning entities, in the style of `Gizmos`.
+///
+/// Shapes must be drawn each frame that they should be visible.
+/// They are sorted and batched with the other shapes of the shader, with shapes with equal sort keys drawn in the order they were drawn in.
+/// The shader must be used by an `ExtractToShaderPlugin` or a `MultiShapeInstancesPlugin`.
+#[derive(SystemParam)]
+pub struct ParamShapes<'w, Shader: ParameterizedShader> {
+    shapes: ResMut<'w, ImmediateShapes<Shader>>,
+}
+
+impl<'w, Shader: ParameterizedShader> ParamShapes<'w, Shader> {
+    /// Draws a shape with these params this frame
+    pub fn draw(&mut self, transform: impl Into<GlobalTransform>, params: Shader::Params) {
+        self.draw_with(transform, params);
+    }
+
+    /// Draws a shape with these params this frame, returning the shape so that its layers and sorting can be set.
+    /// By default it is drawn like an entity with only a transform.
+    ///
+    /// ```no_run
+    /// # use bevy::{prelude::*, render::view::RenderLayers};
+    /// # use bevy_param_shaders::{prelude::*, primitives::CircleShader};
+    /// fn draw_minimap_marker(mut shapes: ParamShapes<CircleShader>) {
+    ///     shapes
+    ///         .draw_with(Transform::from_scale(Vec3::splat(10.0)), Color::WHITE.into())
+    ///         .with_layers(RenderLayers::layer(1))
+    ///         .with_z_index(ShaderZIndex::new(1, 0.0));
+    /// }
+    /// ```
+    pub fn draw_with(
+        &mut self,
+        transform: impl Into<GlobalTransform>,
+        params: Shader::Params,
+    ) -> &mut ImmediateShape<Shader::Params> {
+        self.shapes.shapes.push(ImmediateShape {
+            transform: transform.into(),
+            params,
+            layers: RenderLayers::default(),
+            z_index: None,
+            y_offset: None,
+        });
+        self.shapes.shapes.last_mut().unwrap()
+    }
+}
+
+/// A shape drawn with [`ParamShapes`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImmediateShape<Params> {
+    transform: GlobalTransform,
+    params: Params,
+    layers: RenderLayers,
+    z_index: Option<ShaderZIndex>,
+    y_offset: Option<ShaderYSortOffset>,
+}
+
+impl<Params> ImmediateShape<Params> {
+    /// Only draw the shape with cameras on these layers, like the `RenderLayers` of an entity
+    pub fn with_layers(&mut self, layers: RenderLayers) -> &mut Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sort the shape by this layer and order, like the `ShaderZIndex` of an entity
+    pub fn with_z_index(&mut self, z_index: ShaderZIndex) -> &mut Self {
+        self.z_index = Some(z_index);
+        self
+    }
+
+    /// Offset the y the shape is sorted by, like the `ShaderYSortOffset` of an entity
+    pub fn with_y_sort_offset(&mut self, y_offset: ShaderYSortOffset) -> &mut Self {
+        self.y_offset = Some(y_offset);
+        self
+    }
+}
+
+/// The shapes drawn with [`ParamShapes`] this frame
+#[derive(Resource)]
+pub struct ImmediateShapes<Shader: ParameterizedShader> {
+    shapes: Vec<ImmediateShape<Shader::Params>>,
+}
+
+impl<Shader: ParameterizedShader> Default for ImmediateShapes<Shader> {
+    fn default() -> Self {
+        Self {
+            shapes: Default::default(),
+        }
+    }
+}
+
+/// Clears the shapes of the last frame, before any are drawn for this one
+pub(crate) fn clear_immediate_shapes<Shader: ParameterizedShader>(
+    mut shapes: ResMut<ImmediateShapes<Shader>>,
+) {
+    shapes.shapes.clear();
+}
+
+pub(crate) fn extract_immediate_shapes<Shader: ParameterizedShader>(
+    mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
+    shapes: Extract<Res<ImmediateShapes<Shader>>>,
+) {
+    let extractor = TypeId::of::<ImmediateShapes<Shader>>();
+
+    // each shape reuses the slot of the shape drawn in the same position last frame
+    for (index, shape) in shapes.shapes.iter().enumerate() {
+        let vertex = ShapeVertex::new(&shape.transform, shape.params);
+
+        extracted_shapes.add(ExtractedShape::Visible {
+            key: ShapeKey {
+                entity: IMMEDIATE_SHAPE_ENTITY,
+                extractor,
+                index: index as u32,
+            },
+            layers: shape.layers.clone(),
+            image: None,
+            sort: ShapeSortInputs::new(
+                &shape.transform,
+                shape.z_index.as_ref(),
+                shape.y_offset.as_ref(),
+            ),
+            bounds: vertex.bounds(Shader::frame(&shape.params)),
+            vertex,
+            archetype: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, render::MainWorld};
+
+    use super::*;
+    use crate::{
+        primitives::CircleShader, shader_params::ColorParams, sorting::ShapeSortKey,
+        ParamShaderSortMode,
+    };
+
+    #[test]
+    fn drawn_shapes_are_extracted() {
+        let mut main_world = MainWorld::default();
+        main_world.init_resource::<ImmediateShapes<CircleShader>>();
+        main_world.run_system_once(|mut shapes: ParamShapes<CircleShader>| {
+            shapes.draw(Transform::from_xyz(0.0, 0.0, 3.0), ColorParams::default());
+            shapes
+                .draw_with(Transform::default(), ColorParams::default())
+                .with_layers(RenderLayers::layer(1))
+                .with_z_index(ShaderZIndex::new(2, 0.5));
+        });
+
+        let mut render_world = World::new();
+        render_world.insert_resource(main_world);
+        render_world.init_resource::<ExtractedShapes<CircleShader>>();
+        render_world.run_system_once(extract_immediate_shapes::<CircleShader>);
+
+        let extracted = render_world.resource::<ExtractedShapes<CircleShader>>();
+        let drawn: Vec<_> = extracted
+            .instances
+            .iter()
+            .map(|instance| {
+                (
+                    instance.layers.clone(),
+                    instance.sort.key(ParamShaderSortMode::Z),
+                )
+            })
+            .collect();
+        assert_eq!(
+            drawn,
+            [
+                (
+                    RenderLayers::default(),
+                    ShapeSortKey {
+                        depth: 3.0,
+                        order: 0.0
+                    }
+                ),
+                (
+                    RenderLayers::layer(1),
+                    ShapeSortKey {
+                        depth: 2.0,
+                        order: 0.5
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::any::TypeId;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Buffer, BufferDescriptor, BufferUsages},
+        renderer::{RenderDevice, RenderQueue},
+    },
+    utils::HashMap,
+};
+
+use crate::{shader_params::ShaderParams, ShapeVertex};
+
+/// Identifies one instance of a shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ShapeKey {
+    pub entity: Entity,
+    /// The `ExtractToShader` that extracted this instance
+    pub extractor: TypeId,
+    /// The position of this instance among the instances drawn for the entity
+    pub index: u32,
+}
+
+/// A persistent GPU buffer of shape instances.
+///
+/// Each shape keeps the same slot for as long as it exists, and only slots which have changed are uploaded.
+/// Shapes which are not extracted in a frame have their slots freed.
+pub(crate) struct InstanceBuffer<PARAMS: ShaderParams> {
+    slots: HashMap<ShapeKey, u32>,
+    keys: Vec<Option<ShapeKey>>,
+    data: Vec<ShapeVertex<PARAMS>>,
+    seen: Vec<bool>,
+    free: Vec<u32>,
+    dirty: Vec<u32>,
+    buffer: Option<Buffer>,
+    /// The number of instances that fit in `buffer`
+    capacity: usize,
+}
+
+impl<PARAMS: ShaderParams> Default for InstanceBuffer<PARAMS> {
+    fn default() -> Self {
+        Self {
+            slots: Default::default(),
+            keys: Default::default(),
+            data: Default::default(),
+            seen: Default::default(),
+            free: Default::default(),
+            dirty: Default::default(),
+            buffer: None,
+            capacity: 0,
+        }
+    }
+}
+
+impl<PARAMS: ShaderParams> InstanceBuffer<PARAMS> {
+    const MIN_CAPACITY: usize = 64;
+
+    /// Sets the data for this shape, returning its slot.
+    /// The slot will only be uploaded if the data has changed.
+    pub fn insert(&mut self, key: ShapeKey, vertex: ShapeVertex<PARAMS>) -> u32 {
+        if let Some(slot) = self.slots.get(&key).copied() {
+            let index = slot as usize;
+            self.seen[index] = true;
+            if bytemuck::bytes_of(&self.data[index]) != bytemuck::bytes_of(&vertex) {
+                self.data[index] = vertex;
+                self.dirty.push(slot);
+            }
+            return slot;
+        }
+
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                let index = slot as usize;
+                self.keys[index] = Some(key);
+                self.data[index] = vertex;
+                self.seen[index] = true;
+                slot
+            }
+            None => {
+                self.keys.push(Some(key));
+                self.data.push(vertex);
+                self.seen.push(true);
+                (self.data.len() - 1) as u32
+            }
+        };
+
+        self.slots.insert(key, slot);
+        self.dirty.push(slot);
+        slot
+    }
+
+    /// The slot of this shape, if it has one
+    pub fn slot(&self, key: &ShapeKey) -> Option<u32> {
+        self.slots.get(key).copied()
+    }
+
+    /// Keep the slot for this shape without changing its data, e.g. because it is not visible this frame
+    pub fn keep(&mut self, key: &ShapeKey) {
+        if let Some(slot) = self.slots.get(key) {
+            self.seen[*slot as usize] = true;
+        }
+    }
+
+    /// Frees the slots of all shapes which were not inserted or kept since the last call
+    pub fn remove_unseen(&mut self) {
+        for (index, seen) in self.seen.iter_mut().enumerate() {
+            if !*seen {
+                if let Some(key) = self.keys[index].take() {
+                    self.slots.remove(&key);
+                    self.free.push(index as u32);
+                }
+            }
+            *seen = false;
+        }
+    }
+
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
+
+    /// Uploads changed slots to the GPU, recreating the buffer if it is too small
+    pub fn write_buffer(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        if self.data.is_empty() {
+            self.dirty.clear();
+            return;
+        }
+
+        let stride = std::mem::size_of::<ShapeVertex<PARAMS>>();
+
+        if self.buffer.is_none() || self.capacity < self.data.len() {
+            self.capacity = self.data.len().next_power_of_two().max(Self::MIN_CAPACITY);
+            let buffer = render_device.create_buffer(&BufferDescriptor {
+                label: Some("param_shader_instance_buffer"),
+                size: (self.capacity * stride) as u64,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            render_queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&self.data));
+            self.buffer = Some(buffer);
+            self.dirty.clear();
+            return;
+        }
+
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+
+        self.dirty.sort_unstable();
+        self.dirty.dedup();
+
+        // write runs of adjacent slots together
+        let mut index = 0;
+        while let Some(start) = self.dirty.get(index).copied() {
+            let mut end = start + 1;
+            index += 1;
+            while self.dirty.get(index) == Some(&end) {
+                end += 1;
+                index += 1;
+            }
+
+            let range = (start as usize)..(end as usize);
+            render_queue.write_buffer(
+                buffer,
+                (range.start * stride) as u64,
+                bytemuck::cast_slice(&self.data[range]),
+            );
+        }
+
+        self.dirty.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_params::NoParams;
+
+    fn key(index: u32) -> ShapeKey {
+        ShapeKey {
+            entity: Entity::from_raw(index),
+            extractor: TypeId::of::<()>(),
+            index: 0,
+        }
+    }
+
+    fn vertex(x: f32) -> ShapeVertex<NoParams> {
+        ShapeVertex::new(&GlobalTransform::from_xyz(x, 0.0, 0.0), NoParams)
+    }
+
+    #[test]
+    fn shapes_keep_their_slots() {
+        let mut buffer = InstanceBuffer::<NoParams>::default();
+        assert_eq!(buffer.insert(key(0), vertex(0.0)), 0);
+        assert_eq!(buffer.insert(key(1), vertex(1.0)), 1);
+        buffer.remove_unseen();
+
+        // hidden shapes keep their slots
+        buffer.keep(&key(0));
+        assert_eq!(buffer.insert(key(1), vertex(2.0)), 1);
+        buffer.remove_unseen();
+        assert_eq!(buffer.slot(&key(0)), Some(0));
+        assert_eq!(buffer.slot(&key(1)), Some(1));
+    }
+
+    #[test]
+    fn unseen_slots_are_reused() {
+        let mut buffer = InstanceBuffer::<NoParams>::default();
+        buffer.insert(key(0), vertex(0.0));
+        buffer.insert(key(1), vertex(1.0));
+        buffer.remove_unseen();
+
+        buffer.keep(&key(1));
+        buffer.remove_unseen();
+        assert_eq!(buffer.slot(&key(0)), None);
+
+        assert_eq!(buffer.insert(key(2), vertex(2.0)), 0);
+        assert_eq!(buffer.slot(&key(1)), Some(1));
+    }
+
+    #[test]
+    fn only_changed_slots_are_uploaded() {
+        let mut buffer = InstanceBuffer::<NoParams>::default();
+        buffer.insert(key(0), vertex(0.0));
+        buffer.insert(key(1), vertex(1.0));
+        assert_eq!(buffer.dirty, [0, 1]);
+        buffer.dirty.clear();
+
+        buffer.insert(key(0), vertex(0.0));
+        buffer.insert(key(1), vertex(3.0));
+        assert_eq!(buffer.dirty, [1]);
+    }
+}
@@ -1,61 +1,99 @@
 //#![warn(missing_docs)]
 #![allow(clippy::too_many_arguments)]
+#![allow(clippy::type_complexity)]
 
-use std::{marker::PhantomData, ops::Range};
+use std::{any::TypeId, marker::PhantomData, ops::Range};
 
 use bevy::{
     core_pipeline::core_2d::Transparent2d,
     ecs::{
+        archetype::ArchetypeId,
+        entity::{EntityHashMap, EntityHashSet},
         query::ROQueryItem,
         system::{
             lifetimeless::{Read, SRes},
-            StaticSystemParam, SystemParamItem,
+            StaticSystemParam, SystemChangeTick, SystemParamItem,
         },
+        world::EntityRef,
     },
     math::{FloatOrd, Vec3Swizzles},
     prelude::*,
     render::{
+        extract_resource::ExtractResourcePlugin,
         globals::GlobalsBuffer,
+        graph::CameraDriverLabel,
+        render_asset::RenderAssets,
+        render_graph::RenderGraph,
         render_phase::{
-            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
-            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+            sort_phase_system, DrawFunctionId, DrawFunctions, PhaseItem, PhaseItemExtraIndex,
+            RenderCommand, RenderCommandResult, RenderCommandState, SetItemPipeline,
+            TrackedRenderPass, ViewSortedRenderPhases,
         },
         render_resource::{
-            BindGroup, BindGroupEntries, BufferUsages, PipelineCache, PrimitiveTopology,
-            RawBufferVec, SpecializedRenderPipelines,
+            BindGroup, BindGroupEntries, BufferUsages, CachedPipelineState, CachedRenderPipelineId,
+            PipelineCache, PrimitiveTopology, RawBufferVec, SpecializedRenderPipelines,
+            UniformBuffer,
         },
         renderer::{RenderDevice, RenderQueue},
-        view::{ExtractedView, ViewUniformOffset, ViewUniforms},
+        texture::{FallbackImage, GpuImage},
+        view::{ExtractedView, RenderLayers, ViewUniformOffset, ViewUniforms, VisibleEntities},
         Extract, Render, RenderApp, RenderSet,
     },
+    utils::{HashMap, HashSet, Parallel},
 };
 use bundle::ShaderCheckVisibility;
 use bytemuck::{NoUninit, Zeroable};
+use cache::{ShaderCachePlugin, ShaderCacheState};
 use check_shapes::CheckShapesPlugin;
+use frame::Frame;
+use instance_buffer::{InstanceBuffer, ShapeKey};
 use pipeline_key::PipelineKey;
+use registry::ParamShaderRegistry;
 use shader_loading::*;
 
 use parameterized_shader::*;
 use shader_params::ShaderParams;
 use shader_pipeline::*;
+use shader_uniforms::ShaderUniforms;
+use snapshot::{ShapeSnapshotCamera, ShapeSnapshotPlugin};
+use sorting::{image_sort_group, ShapeSortInputs, SortedInstances};
 
 pub use bundle::ShaderBundle;
 pub use components::*;
 
+mod bounds;
 pub mod bundle;
+pub mod cache;
 mod check_shapes;
+pub mod combined;
 mod components;
+mod cull_shader;
+mod culling;
+pub mod dynamic;
+pub mod fragment_body;
 mod fragment_shader;
 pub mod frame;
 mod helpers;
+pub mod immediate;
+mod instance_buffer;
+pub mod multi_instance;
+mod param_changes;
 pub mod parameterized_shader;
 mod pipeline_key;
+pub mod registry;
+mod shader_code;
 mod shader_loading;
 pub mod shader_params;
 mod shader_pipeline;
+pub mod shader_uniforms;
+mod sorting;
+pub mod style;
+#[cfg(test)]
+mod testing;
 mod vertex_shader;
 
 pub mod primitives;
+pub mod snapshot;
 
 /// Re-export of the essentials needed for rendering shapes
 ///
@@ -65,43 +103,94 @@ pub mod primitives;
 /// ```
 pub mod prelude {
     pub use crate::{
-        bundle::ShaderCheckVisibility, frame::Frame, parameterized_shader::*, shader_params::*,
-        ExtractToShaderPlugin, ShaderBundle, ShaderUsage,
+        bundle::ShaderCheckVisibility,
+        cache::ShaderCacheToTexture,
+        combined::{CombinedExtraction, CombinedShader},
+        dynamic::{
+            DynamicShaderBundle, DynamicShaderDefinition, DynamicShaderParams, DynamicShaderPlugin,
+        },
+        fragment_body::FragmentBody,
+        frame::Frame,
+        immediate::ParamShapes,
+        multi_instance::{
+            MultiShapeBundle, MultiShapeInstances, MultiShapeInstancesPlugin, ShapeInstance,
+        },
+        parameterized_shader::*,
+        registry::{ParamShaderCommandsExt, ParamShaderRegistry, ParamShaderRegistryPlugin},
+        shader_params::*,
+        shader_uniforms::*,
+        style::{ShapeStyle, ShapeStylePlugin, StyledShape},
+        ExtractToShaderPlugin, ParamShaderSortMode, ShaderBundle, ShaderUsage, ShaderYSortOffset,
+        ShaderZIndex, ShapeBatchReordering, ShapeCulling,
     };
 }
 
 pub struct ExtractToShaderPlugin<Extractable: ExtractToShader>(PhantomData<Extractable>);
 
-impl<Extractable: ExtractToShader> Plugin for ExtractToShaderPlugin<Extractable> {
+impl<Extractable: ExtractToShader> Plugin for ExtractToShaderPlugin<Extractable>
+where
+    for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+{
     fn build(&self, app: &mut App) {
+        Extractable::validate();
+
         if !app.is_plugin_added::<ParamShaderPlugin<Extractable::Shader>>() {
             app.add_plugins(ParamShaderPlugin::<Extractable::Shader>::default());
         }
 
         //todo in debug mode add a system to check that all shaders have the right parameters
 
+        app.world_mut()
+            .resource_mut::<ParamShaderRegistry>()
+            .mark_added::<Extractable>();
+        add_extractable_systems::<Extractable>(app.world_mut());
+
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
-            render_app.add_systems(ExtractSchedule, (extract_shapes::<Extractable>,));
+            add_extractable_extract_systems::<Extractable>(render_app.world_mut());
         };
+    }
+}
 
-        #[cfg(debug_assertions)]
+/// Adds the main world systems of an extractable, which only run while it is registered
+fn add_extractable_systems<Extractable: ExtractToShader>(world: &mut World) {
+    world.init_resource::<param_changes::ParamComponents<Extractable>>();
+    world.resource_mut::<Schedules>().add_systems(
+        PostUpdate,
+        (
+            cache::update_shader_caches::<Extractable>.in_set(cache::ShaderCacheSystems::Update),
+            bounds::update_shape_aabbs::<Extractable>
+                .in_set(bevy::render::view::VisibilitySystems::CalculateBounds),
+        )
+            .run_if(registry::is_registered::<Extractable>),
+    );
+
+    #[cfg(debug_assertions)]
+    {
+        let component_id = world.init_component::<ShaderUsage<Extractable>>();
+
+        if let Some(mut rt) =
+            world.get_resource_mut::<crate::check_shapes::RegisteredExtractables>()
         {
-            let component_id = app.world_mut().init_component::<ShaderUsage<Extractable>>();
-
-            if let Some(mut rt) = app
-                .world_mut()
-                .get_resource_mut::<crate::check_shapes::RegisteredExtractables>()
-            {
-                rt.0.insert(component_id);
-            } else {
-                let mut set = bevy::utils::HashSet::new();
-                set.insert(component_id);
-                app.insert_resource(crate::check_shapes::RegisteredExtractables(set));
-            }
+            rt.0.insert(component_id);
+        } else {
+            let mut set = bevy::utils::HashSet::new();
+            set.insert(component_id);
+            world.insert_resource(crate::check_shapes::RegisteredExtractables(set));
         }
     }
 }
 
+/// Adds the render world systems which extract the shapes of an extractable while it is registered
+fn add_extractable_extract_systems<Extractable: ExtractToShader>(render_world: &mut World)
+where
+    for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+{
+    render_world.resource_mut::<Schedules>().add_systems(
+        ExtractSchedule,
+        extract_shapes::<Extractable>.run_if(registry::is_registered_in_main_world::<Extractable>),
+    );
+}
+
 impl<Extractable: ExtractToShader> Default for ExtractToShaderPlugin<Extractable> {
     fn default() -> Self {
         Self(Default::default())
@@ -113,13 +202,61 @@ struct ParameterShadersPlugin;
 
 impl Plugin for ParameterShadersPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<ParamShaderSortMode>()
+            .init_resource::<ShapeBatchReordering>()
+            .init_resource::<ShapeCulling>()
+            .init_resource::<ParamShaderRegistry>()
+            .init_asset::<fragment_body::FragmentBody>()
+            .init_asset_loader::<fragment_body::FragmentBodyLoader>()
+            .add_systems(Last, registry::setup_registered_shaders)
+            .add_plugins((
+                ExtractResourcePlugin::<ShapeBatchReordering>::default(),
+                ExtractResourcePlugin::<ShapeCulling>::default(),
+            ));
+
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
-            render_app.add_systems(
-                Render,
-                join_adjacent_batches.in_set(RenderSet::PrepareBindGroups),
-            );
+            render_app
+                .init_resource::<ViewsAwaitingPipelines>()
+                .init_resource::<ViewShapeBatches>()
+                .init_resource::<culling::CullDispatches>()
+                .init_resource::<registry::PendingExtractSystems>()
+                .add_systems(
+                    ExtractSchedule,
+                    (
+                        extract_view_visible_shapes,
+                        registry::setup_registered_shaders_render,
+                    ),
+                )
+                .add_systems(
+                    Render,
+                    (
+                        sorting::sort_shape_ties
+                            .in_set(RenderSet::PhaseSort)
+                            .after(sort_phase_system::<Transparent2d>),
+                        sorting::reorder_non_overlapping_batches
+                            .in_set(RenderSet::PhaseSort)
+                            .after(sorting::sort_shape_ties),
+                        join_adjacent_batches.in_set(RenderSet::PrepareBindGroups),
+                        (
+                            clear_views_awaiting_pipelines,
+                            clear_view_shape_batches,
+                            culling::clear_cull_dispatches,
+                            registry::add_pending_extract_systems,
+                        )
+                            .in_set(RenderSet::Cleanup),
+                    ),
+                );
+
+            let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
+            render_graph.add_node(culling::ShapeCullLabel, culling::ShapeCullNode);
+            render_graph.add_node_edge(culling::ShapeCullLabel, CameraDriverLabel);
         };
 
+        if !app.is_plugin_added::<ShapeSnapshotPlugin>() {
+            app.add_plugins(ShapeSnapshotPlugin);
+        }
+        app.add_plugins(ShaderCachePlugin);
+
         //todo improve check visibility
         app.add_systems(
             PostUpdate,
@@ -146,41 +283,93 @@ impl<Shader: ParameterizedShader> Default for ParamShaderPlugin<Shader> {
 
 impl<Shader: ParameterizedShader> Plugin for ParamShaderPlugin<Shader> {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ShaderLoadingPlugin::<Shader>::default());
         if !app.is_plugin_added::<ParameterShadersPlugin>() {
             app.add_plugins(ParameterShadersPlugin);
         }
 
         //todo in debug mode add a system to check that all shaders have the right parameters
 
+        app.world_mut()
+            .resource_mut::<ParamShaderRegistry>()
+            .mark_shader_added::<Shader>();
+        add_shader_systems::<Shader>(app.world_mut());
+
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
-            render_app
-                .add_render_command::<Transparent2d, DrawShaderShape<Shader>>()
-                .init_resource::<ExtractedShapes<Shader>>()
-                .init_resource::<SpecializedRenderPipelines<ShaderPipeline<Shader>>>()
-                .add_systems(
-                    Render,
-                    (
-                        (sort_shapes::<Shader>, queue_shapes::<Shader>)
-                            .chain()
-                            .in_set(RenderSet::Queue),
-                        prepare_shapes::<Shader>.in_set(RenderSet::PrepareBindGroups),
-                        cleanup_shapes::<Shader>.in_set(RenderSet::Cleanup),
-                    ),
-                );
+            add_shader_render_systems::<Shader>(render_app.world_mut());
+            add_shader_extract_systems::<Shader>(render_app.world_mut());
         };
     }
 
     fn finish(&self, app: &mut App) {
-        app.get_sub_app_mut(RenderApp)
-            .unwrap()
-            .init_resource::<ShaderPipeline<Shader>>();
+        init_shader_pipelines::<Shader>(app.get_sub_app_mut(RenderApp).unwrap().world_mut());
+    }
+}
+
+/// Loads the shaders of a shader and adds its main world systems
+fn add_shader_systems<Shader: ParameterizedShader>(world: &mut World) {
+    load_shaders::<Shader>(world);
+
+    if Shader::FRAGMENT_BODY_PATH.is_some() {
+        world
+            .resource_mut::<Schedules>()
+            .add_systems(Update, update_fragment_body::<Shader>);
     }
+
+    world.init_resource::<immediate::ImmediateShapes<Shader>>();
+    world
+        .resource_mut::<Schedules>()
+        .add_systems(First, immediate::clear_immediate_shapes::<Shader>);
+}
+
+/// Adds the draw function, resources and render systems of a shader, other than its pipelines
+fn add_shader_render_systems<Shader: ParameterizedShader>(render_world: &mut World) {
+    let draw_function =
+        RenderCommandState::<Transparent2d, DrawShaderShape<Shader>>::new(render_world);
+    render_world
+        .resource::<DrawFunctions<Transparent2d>>()
+        .write()
+        .add_with::<DrawShaderShape<Shader>, _>(draw_function);
+
+    render_world.init_resource::<ExtractedShapes<Shader>>();
+    render_world.init_resource::<SpecializedRenderPipelines<ShaderPipeline<Shader>>>();
+    render_world.resource_mut::<Schedules>().add_systems(
+        Render,
+        (
+            (sort_shapes::<Shader>, queue_shapes::<Shader>)
+                .chain()
+                .in_set(RenderSet::Queue),
+            prepare_shapes::<Shader>.in_set(RenderSet::PrepareBindGroups),
+            culling::cull_shapes::<Shader>
+                .in_set(RenderSet::PrepareBindGroups)
+                .after(prepare_shapes::<Shader>)
+                .after(join_adjacent_batches),
+            cleanup_shapes::<Shader>.in_set(RenderSet::Cleanup),
+        ),
+    );
+}
+
+fn add_shader_extract_systems<Shader: ParameterizedShader>(render_world: &mut World) {
+    render_world.resource_mut::<Schedules>().add_systems(
+        ExtractSchedule,
+        (
+            extract_uniforms::<Shader>.run_if(|| Shader::Uniforms::ENABLED),
+            immediate::extract_immediate_shapes::<Shader>,
+        ),
+    );
+}
+
+/// Creates the pipelines of a shader, which needs the render device
+fn init_shader_pipelines<Shader: ParameterizedShader>(render_world: &mut World) {
+    render_world.init_resource::<ShaderPipeline<Shader>>();
+    render_world.init_resource::<culling::ShapeCullPipeline<Shader>>();
 }
 
 type DrawShaderShape<Shader> = (
     SetItemPipeline,
     SetShapeViewBindGroup<0, Shader>,
+    SetShapeInstanceBindGroup<1, Shader>,
+    SetShapeUniformsBindGroup<2, Shader>,
+    SetShapeTextureBindGroup<Shader>,
     DrawShapeBatch<Shader>,
 );
 
@@ -208,22 +397,113 @@ impl<P: PhaseItem, const I: usize, Shader: ParameterizedShader> RenderCommand<P>
     }
 }
 
-struct DrawShapeBatch<Shader: ParameterizedShader>(PhantomData<Shader>);
-impl<P: PhaseItem, Shader: ParameterizedShader> RenderCommand<P> for DrawShapeBatch<Shader> {
+struct SetShapeInstanceBindGroup<const I: usize, Shader: ParameterizedShader>(PhantomData<Shader>);
+impl<P: PhaseItem, const I: usize, Shader: ParameterizedShader> RenderCommand<P>
+    for SetShapeInstanceBindGroup<I, Shader>
+{
     type Param = SRes<ExtractedShapes<Shader>>;
     type ViewQuery = ();
-    type ItemQuery = Read<ShapeBatch>;
+    type ItemQuery = ();
 
     fn render<'w>(
         _item: &P,
         _view: (),
-        batch: Option<&'_ ShapeBatch>,
+        _entity: Option<()>,
         shape_meta: SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(instance_bind_group) = shape_meta.into_inner().instance_bind_group.as_ref() else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, instance_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct SetShapeUniformsBindGroup<const I: usize, Shader: ParameterizedShader>(PhantomData<Shader>);
+impl<P: PhaseItem, const I: usize, Shader: ParameterizedShader> RenderCommand<P>
+    for SetShapeUniformsBindGroup<I, Shader>
+{
+    type Param = SRes<ExtractedShapes<Shader>>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        _entity: Option<()>,
+        shape_meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if !Shader::Uniforms::ENABLED {
+            return RenderCommandResult::Success;
+        }
+        let Some(uniforms_bind_group) = shape_meta.into_inner().uniforms_bind_group.as_ref() else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, uniforms_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct SetShapeTextureBindGroup<Shader: ParameterizedShader>(PhantomData<Shader>);
+impl<P: PhaseItem, Shader: ParameterizedShader> RenderCommand<P>
+    for SetShapeTextureBindGroup<Shader>
+{
+    type Param = (SRes<ExtractedShapes<Shader>>, SRes<ViewShapeBatches>);
+    type ViewQuery = Entity;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: Entity,
+        _entity: Option<()>,
+        (shape_meta, view_batches): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if !Shader::USE_TEXTURE {
+            return RenderCommandResult::Success;
+        }
+        // the image may not have loaded yet
+        let Some(bind_group) = view_batches
+            .get(view, item.extra_index())
+            .and_then(|batch| {
+                shape_meta
+                    .into_inner()
+                    .texture_bind_groups
+                    .get(&batch.image)
+            })
+        else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(texture_group_index::<Shader>(), bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct DrawShapeBatch<Shader: ParameterizedShader>(PhantomData<Shader>);
+impl<P: PhaseItem, Shader: ParameterizedShader> RenderCommand<P> for DrawShapeBatch<Shader> {
+    type Param = (SRes<ExtractedShapes<Shader>>, SRes<ViewShapeBatches>);
+    type ViewQuery = Entity;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: Entity,
+        _entity: Option<()>,
+        (shape_meta, view_batches): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let shape_meta = shape_meta.into_inner();
-        if let Some(batch) = batch {
-            if let Some(buffer) = shape_meta.vertices.buffer() {
+        if let Some(batch) = view_batches.into_inner().get(view, item.extra_index()) {
+            if let Some((culled, offset, draw_args, args_offset)) = batch
+                .culled
+                .and_then(|index| shape_meta.cull_buffers.get(index))
+            {
+                pass.set_vertex_buffer(0, culled.slice(offset..));
+                pass.draw_indirect(draw_args, args_offset);
+                RenderCommandResult::Success
+            } else if let Some(buffer) = shape_meta.indices.buffer() {
                 pass.set_vertex_buffer(0, buffer.slice(..));
                 pass.draw(0..4, batch.range.clone()); //0..4 as there are four vertices
                 RenderCommandResult::Success
@@ -239,16 +519,109 @@ impl<P: PhaseItem, Shader: ParameterizedShader> RenderCommand<P> for DrawShapeBa
 
 #[derive(Resource)]
 struct ExtractedShapes<Shader: ParameterizedShader> {
-    vertices: RawBufferVec<ShapeVertex<Shader::Params>>,
+    /// The shapes to draw this frame
+    instances: Vec<ExtractedInstance>,
+    /// The order of `instances` for each sort mode used by a view
+    sorted: SortedInstances,
+    /// Persistent instance data for every shape, including those not drawn this frame
+    instance_buffer: InstanceBuffer<Shader::Params>,
+    /// The sort inputs and bounds of the shape in each slot, if it was drawn when it was last extracted,
+    /// and the archetype its entity had then
+    drawn: Vec<Option<(ShapeSortInputs, Rect, Option<ArchetypeId>)>>,
+    /// The slots of the instances to draw, in sorted order for each sort mode
+    indices: RawBufferVec<u32>,
+    /// The batches culled on the GPU this frame
+    cull_buffers: culling::CullBuffers,
+    /// The uniforms of the shader, set once per frame
+    uniforms: UniformBuffer<Shader::Uniforms>,
     view_bind_group: Option<BindGroup>,
+    instance_bind_group: Option<BindGroup>,
+    uniforms_bind_group: Option<BindGroup>,
+    /// The bind group of each image used by a shape, `None` is used by shapes without an image
+    texture_bind_groups: HashMap<Option<AssetId<Image>>, BindGroup>,
 }
 
 impl<Shader: ParameterizedShader> Default for ExtractedShapes<Shader> {
     fn default() -> Self {
         Self {
-            // shapes: Default::default(),
-            vertices: RawBufferVec::new(BufferUsages::VERTEX),
+            instances: Default::default(),
+            sorted: Default::default(),
+            instance_buffer: Default::default(),
+            drawn: Default::default(),
+            indices: RawBufferVec::new(BufferUsages::VERTEX | BufferUsages::STORAGE),
+            cull_buffers: Default::default(),
+            uniforms: Default::default(),
             view_bind_group: None,
+            instance_bind_group: None,
+            uniforms_bind_group: None,
+            texture_bind_groups: Default::default(),
+        }
+    }
+}
+
+impl<Shader: ParameterizedShader> ExtractedShapes<Shader> {
+    /// The instance of a shape which has not changed since it was last extracted, if it was drawn then.
+    /// Shapes whose entity has moved to another archetype since then have had components removed, so are not unchanged
+    fn unchanged_instance(
+        &self,
+        key: &ShapeKey,
+        archetype: ArchetypeId,
+        layers: RenderLayers,
+        image: Option<AssetId<Image>>,
+    ) -> Option<ExtractedInstance> {
+        let slot = self.instance_buffer.slot(key)?;
+        let (sort, bounds, previous_archetype) = (*self.drawn.get(slot as usize)?)?;
+        if previous_archetype != Some(archetype) {
+            return None;
+        }
+
+        Some(ExtractedInstance {
+            entity: key.entity,
+            layers,
+            image,
+            slot,
+            sort,
+            bounds,
+        })
+    }
+
+    /// Keeps the slot of a hidden shape, or updates the slot of a visible shape and draws it this frame
+    fn add(&mut self, shape: ExtractedShape<Shader::Params>) {
+        match shape {
+            ExtractedShape::Hidden(key) => {
+                self.instance_buffer.keep(&key);
+                if let Some(slot) = self.instance_buffer.slot(&key) {
+                    self.drawn[slot as usize] = None;
+                }
+            }
+            ExtractedShape::Unchanged(key, instance) => {
+                self.instance_buffer.keep(&key);
+                self.instances.push(instance);
+            }
+            ExtractedShape::Visible {
+                key,
+                layers,
+                image,
+                sort,
+                bounds,
+                vertex,
+                archetype,
+            } => {
+                let slot = self.instance_buffer.insert(key, vertex);
+                if self.drawn.len() <= slot as usize {
+                    self.drawn.resize(slot as usize + 1, None);
+                }
+                self.drawn[slot as usize] = Some((sort, bounds, archetype));
+
+                self.instances.push(ExtractedInstance {
+                    entity: key.entity,
+                    layers,
+                    image,
+                    slot,
+                    sort,
+                    bounds,
+                });
+            }
         }
     }
 }
@@ -260,38 +633,155 @@ fn extract_shapes<'w, Extractable: ExtractToShader>(
             'w,
             '_,
             (
+                Entity,
+                EntityRef,
                 &ViewVisibility,
                 Extractable::ParamsQuery<'_>,
                 &GlobalTransform,
+                Option<&RenderLayers>,
+                Option<&ShaderZIndex>,
+                Option<&ShaderYSortOffset>,
+                Option<&ShaderCacheState>,
+                Option<&Handle<Image>>,
             ),
-            With<ShaderUsage<Extractable>>,
+            (With<ShaderUsage<Extractable>>, Extractable::Filter),
         >,
     >,
+    param_components: Extract<Res<param_changes::ParamComponents<Extractable>>>,
+    ticks: Extract<SystemChangeTick>,
     resource_params: Extract<StaticSystemParam<Extractable::ResourceParams<'w>>>,
-) {
-    let resource = resource_params;
-
-    for (view_visibility, params_item, transform) in shape_query.iter() {
-        if !view_visibility.get() {
-            continue;
-        }
+    mut thread_queues: Local<
+        Parallel<Vec<ExtractedShape<<Extractable::Shader as ParameterizedShader>::Params>>>,
+    >,
+) where
+    for<'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+{
+    let resource: &SystemParamItem<Extractable::ResourceParams<'w>> = &resource_params;
+    let extractor = TypeId::of::<Extractable>();
+    let previous: &ExtractedShapes<Extractable::Shader> = &extracted_shapes;
+
+    shape_query.par_iter().for_each(
+        |(
+            entity,
+            entity_ref,
+            view_visibility,
+            params_item,
+            transform,
+            layers,
+            z_index,
+            y_offset,
+            cache_state,
+            image,
+        )| {
+            let key = ShapeKey {
+                entity,
+                extractor,
+                index: 0,
+            };
+            let context = ExtractionContext { entity, transform };
+            let image = image
+                .filter(|_| <Extractable::Shader as ParameterizedShader>::USE_TEXTURE)
+                .map(Handle::id);
+
+            // shapes whose params have not changed are drawn as they were last frame, without extracting their params again
+            if view_visibility.get()
+                && !cache_state.is_some_and(ShaderCacheState::is_ready)
+                && !param_components.changed(&entity_ref, &ticks)
+            {
+                let layers = layers.cloned().unwrap_or_default();
+                let archetype = entity_ref.archetype().id();
+                if let Some(instance) = previous.unchanged_instance(&key, archetype, layers, image)
+                {
+                    thread_queues
+                        .scope(|queue| queue.push(ExtractedShape::Unchanged(key, instance)));
+                    return;
+                }
+            }
 
-        let params = Extractable::get_params(params_item, &resource);
+            // hidden shapes, shapes drawn from their cached texture and shapes which should not be drawn keep their slot but are not drawn
+            let params =
+                if !view_visibility.get() || cache_state.is_some_and(ShaderCacheState::is_ready) {
+                    None
+                } else {
+                    Some(Extractable::get_params_with_context(
+                        params_item,
+                        resource,
+                        &context,
+                    ))
+                    .filter(Extractable::should_draw)
+                };
+            let shape = match params {
+                None => ExtractedShape::Hidden(key),
+                Some(params) => {
+                    let frame = <Extractable::Shader as ParameterizedShader>::frame(&params);
+                    let instance_transform = Extractable::instance_transform(&params, &context);
+                    let vertex = ShapeVertex::new(&instance_transform, params);
+
+                    ExtractedShape::Visible {
+                        key,
+                        layers: layers.cloned().unwrap_or_default(),
+                        image,
+                        sort: ShapeSortInputs::new(&instance_transform, z_index, y_offset),
+                        bounds: vertex.bounds(frame),
+                        vertex,
+                        archetype: Some(entity_ref.archetype().id()),
+                    }
+                }
+            };
 
-        let shape_vertex = ShapeVertex::new(transform, params);
+            thread_queues.scope(|queue| queue.push(shape));
+        },
+    );
 
-        extracted_shapes.vertices.push(shape_vertex);
+    // slots are assigned on a single thread
+    for queue in thread_queues.iter_mut() {
+        for shape in queue.drain(..) {
+            extracted_shapes.add(shape);
+        }
     }
 }
 
-fn sort_shapes<Shader: ParameterizedShader>(mut extracted_shapes: ResMut<ExtractedShapes<Shader>>) {
-    radsort::sort_by_key(extracted_shapes.as_mut().vertices.values_mut(), |item| {
-        item.z_index()
-    });
+fn extract_uniforms<'w, Shader: ParameterizedShader>(
+    mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
+    resource_params: Extract<
+        StaticSystemParam<<Shader::Uniforms as ShaderUniforms>::ResourceParams<'w>>,
+    >,
+) {
+    let resource: &SystemParamItem<<Shader::Uniforms as ShaderUniforms>::ResourceParams<'w>> =
+        &resource_params;
+
+    extracted_shapes
+        .uniforms
+        .set(Shader::Uniforms::get_uniforms(resource));
+}
+
+fn sort_shapes<Shader: ParameterizedShader>(
+    mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
+    views: Query<Option<&ParamShaderSortMode>, With<ExtractedView>>,
+) {
+    let extracted_shapes = extracted_shapes.as_mut();
+    // every shape that still exists has been extracted by now
+    extracted_shapes.instance_buffer.remove_unseen();
+
+    // Extraction happens in parallel so the order of shapes with equal keys is decided by their entity
+    extracted_shapes.indices.clear();
+    extracted_shapes.sorted.sort(
+        views.iter().map(|mode| mode.copied().unwrap_or_default()),
+        &extracted_shapes.instances,
+        |item, mode| {
+            (
+                item.sort.key(mode),
+                image_sort_group(item.image),
+                item.entity,
+            )
+        },
+        |item| {
+            extracted_shapes.indices.push(item.slot);
+        },
+    );
 }
 
 fn queue_shapes<Shader: ParameterizedShader>(
-    mut commands: Commands,
     draw_functions: Res<DrawFunctions<Transparent2d>>,
     pipeline: Res<ShaderPipeline<Shader>>,
     mut pipelines: ResMut<SpecializedRenderPipelines<ShaderPipeline<Shader>>>,
@@ -299,18 +789,28 @@ fn queue_shapes<Shader: ParameterizedShader>(
     msaa: Res<Msaa>,
     extracted_shapes: Res<ExtractedShapes<Shader>>,
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
-    views: Query<(Entity, &ExtractedView)>,
+    mut views_awaiting_pipelines: ResMut<ViewsAwaitingPipelines>,
+    mut view_batches: ResMut<ViewShapeBatches>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        Option<&ViewVisibleShapes>,
+        Option<&RenderLayers>,
+        Option<&ParamShaderSortMode>,
+    )>,
 ) {
     let draw_function = draw_functions
         .read()
         .get_id::<DrawShaderShape<Shader>>()
         .unwrap();
+    view_batches.register_draw_function(draw_function);
 
     // Iterate over each view (a camera is a view)
-    for (view_entity, view) in views.iter() {
+    for (view_entity, view, visible_shapes, view_layers, sort_mode) in views.iter() {
         let Some(transparent_phase) = transparent_render_phases.get_mut(&view_entity) else {
             continue;
         };
+        let view_layers = view_layers.cloned().unwrap_or_default();
         // todo: bevy_sprite does some hdr stuff, should we?
         // let mut view_key = SpritePipelineKey::from_hdr(view.hdr) | msaa_key;
 
@@ -323,35 +823,67 @@ fn queue_shapes<Shader: ParameterizedShader>(
         };
         let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, specialize_key);
 
-        let mut index = 0;
-        while let Some(first_shape) = extracted_shapes.vertices.values().get(index) {
-            let start = index;
-            index += 1;
-            let z = first_shape.z_index();
-            //these will always be batched with shapes with the same z index
-            while extracted_shapes
-                .vertices
-                .values()
-                .get(index)
-                .is_some_and(|n| n.z_index() == z)
-            {
-                index += 1;
+        let instances = &extracted_shapes.instances;
+
+        let is_drawn = |instance: &ExtractedInstance| {
+            is_visible_in_view(
+                instance.entity,
+                &instance.layers,
+                visible_shapes,
+                &view_layers,
+            )
+        };
+
+        let sort_mode = sort_mode.copied().unwrap_or_default();
+        let mut sorted = extracted_shapes
+            .sorted
+            .iter(sort_mode, instances)
+            .peekable();
+
+        // views only wait for the pipeline when they draw some of its shapes
+        let mut draws_shapes = false;
+        while let Some((start, first_shape)) = sorted.next() {
+            if !is_drawn(first_shape) {
+                continue;
+            }
+            draws_shapes = true;
+            let sort_key = first_shape.sort.key(sort_mode);
+            let mut end = start + 1;
+            let mut bounds = first_shape.bounds;
+            //these will always be batched with shapes with the same sort key and image
+            while let Some((index, shape)) = sorted.next_if(|(_, n)| {
+                n.sort.key(sort_mode) == sort_key && n.image == first_shape.image && is_drawn(n)
+            }) {
+                end = index + 1;
+                bounds = bounds.union(shape.bounds);
             }
 
-            let sort_key = FloatOrd(z);
-            let range = (start as u32)..(index as u32);
-            let entity = commands.spawn(ShapeBatch { range }).id();
+            let range = start..end;
+            let extra_index = view_batches.push(
+                view_entity,
+                ShapeBatch {
+                    range,
+                    order: sort_key.order,
+                    image: first_shape.image,
+                    bounds,
+                    culled: None,
+                },
+            );
 
             // Add the item to the render phase
             transparent_phase.add(Transparent2d {
                 draw_function,
                 pipeline,
-                entity,
-                sort_key,
+                entity: first_shape.entity,
+                sort_key: FloatOrd(sort_key.depth),
                 batch_range: 0..1,
-                extra_index: PhaseItemExtraIndex::NONE,
+                extra_index,
             });
         }
+
+        if draws_shapes {
+            views_awaiting_pipelines.check(view_entity, &pipeline_cache, pipeline);
+        }
     }
 }
 
@@ -362,6 +894,8 @@ fn prepare_shapes<Shader: ParameterizedShader>(
     pipeline: Res<ShaderPipeline<Shader>>,
     mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
     globals_buffer: Res<GlobalsBuffer>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
 ) {
     let Some(view_binding) = view_uniforms.uniforms.binding() else {
         return;
@@ -385,26 +919,90 @@ fn prepare_shapes<Shader: ParameterizedShader>(
         ));
     }
 
-    //info!("Preparing {} shapes", extracted_shapes.vertices.len());
+    //info!("Preparing {} shapes", extracted_shapes.indices.len());
 
     extracted_shapes
-        .vertices
+        .instance_buffer
         .write_buffer(&render_device, &render_queue);
+
+    extracted_shapes.instance_bind_group =
+        extracted_shapes.instance_buffer.buffer().map(|buffer| {
+            render_device.create_bind_group(
+                "param_shader_instance_bind_group",
+                &pipeline.instance_layout,
+                &BindGroupEntries::single(buffer.as_entire_binding()),
+            )
+        });
+
+    extracted_shapes
+        .indices
+        .write_buffer(&render_device, &render_queue);
+
+    if Shader::Uniforms::ENABLED {
+        extracted_shapes
+            .uniforms
+            .write_buffer(&render_device, &render_queue);
+
+        extracted_shapes.uniforms_bind_group = extracted_shapes.uniforms.binding().map(|binding| {
+            render_device.create_bind_group(
+                "param_shader_uniforms_bind_group",
+                &pipeline.uniforms_layout,
+                &BindGroupEntries::single(binding),
+            )
+        });
+    }
+
+    if Shader::USE_TEXTURE {
+        let extracted_shapes = extracted_shapes.as_mut();
+        extracted_shapes.texture_bind_groups.clear();
+        for instance in extracted_shapes.instances.iter() {
+            if extracted_shapes
+                .texture_bind_groups
+                .contains_key(&instance.image)
+            {
+                continue;
+            }
+            let gpu_image = match instance.image {
+                Some(image) => gpu_images.get(image),
+                None => Some(&fallback_image.d2),
+            };
+            let Some(gpu_image) = gpu_image else {
+                continue;
+            };
+            extracted_shapes.texture_bind_groups.insert(
+                instance.image,
+                render_device.create_bind_group(
+                    "param_shader_texture_bind_group",
+                    &pipeline.texture_layout,
+                    &BindGroupEntries::sequential((&gpu_image.texture_view, &gpu_image.sampler)),
+                ),
+            );
+        }
+    }
 }
 
+/// Merges consecutive phase items of each view which draw contiguous ranges of the same instances
 fn join_adjacent_batches(
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
-    mut batches: Query<&mut ShapeBatch>,
+    mut view_batches: ResMut<ViewShapeBatches>,
 ) {
-    for transparent_phase in transparent_render_phases.0.values_mut() {
+    let view_batches = view_batches.as_mut();
+
+    for (view, transparent_phase) in transparent_render_phases.0.iter_mut() {
+        let Some(batches) = view_batches.views.get_mut(view) else {
+            continue;
+        };
         let mut index = 0;
 
         while let Some(item) = transparent_phase.items.get(index) {
             let item_index = index;
             index += 1;
 
-            let entity = item.entity;
-            let Ok(batch) = batches.get(entity) else {
+            if !view_batches.draw_functions.contains(&item.draw_function) {
+                continue;
+            }
+            let batch_index = item.extra_index.0 as usize;
+            let Some(batch) = batches.get(batch_index) else {
                 continue;
             };
             let mut range = batch.range.clone();
@@ -414,10 +1012,13 @@ fn join_adjacent_batches(
                 if item.draw_function != next.draw_function {
                     break 'concat;
                 }
-                let next_entity = next.entity;
-                let Ok(next_batch) = batches.get(next_entity) else {
+                let Some(next_batch) = batches.get(next.extra_index.0 as usize) else {
                     break 'concat;
                 };
+                // shapes which are not drawn by this view may lie between the two batches
+                if next_batch.image != batch.image || next_batch.range.start != range.end {
+                    break 'concat;
+                }
                 range.end = next_batch.range.end;
                 index += 1;
                 extra_count += 1;
@@ -430,12 +1031,8 @@ fn join_adjacent_batches(
                     continue;
                 };
 
-                let Ok(mut batch) = batches.get_mut(entity) else {
-                    continue;
-                };
-
                 item.batch_range.end += extra_count;
-                batch.range = range;
+                batches[batch_index].range = range;
             }
         }
     }
@@ -444,8 +1041,136 @@ fn join_adjacent_batches(
 fn cleanup_shapes<Shader: ParameterizedShader>(
     mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
 ) {
-    //info!("Clearing {} shapes", extracted_shapes.vertices.len());
-    extracted_shapes.vertices.clear();
+    //info!("Clearing {} shapes", extracted_shapes.indices.len());
+    extracted_shapes.instances.clear();
+    extracted_shapes.sorted.clear();
+    extracted_shapes.indices.clear();
+}
+
+pub(crate) fn clear_view_shape_batches(mut view_batches: ResMut<ViewShapeBatches>) {
+    view_batches.views.clear();
+}
+
+pub(crate) fn clear_views_awaiting_pipelines(
+    mut views_awaiting_pipelines: ResMut<ViewsAwaitingPipelines>,
+) {
+    views_awaiting_pipelines.awaiting.clear();
+    views_awaiting_pipelines.failed.clear();
+}
+
+/// Records which shapes each camera can see, so that shapes are only drawn by the views they are visible in,
+/// and how the camera sorts them
+fn extract_view_visible_shapes(
+    mut commands: Commands,
+    cameras: Extract<
+        Query<(
+            Entity,
+            &Camera,
+            &VisibleEntities,
+            Option<&ShapeSnapshotCamera>,
+            Option<&ParamShaderSortMode>,
+        )>,
+    >,
+    default_sort_mode: Extract<Res<ParamShaderSortMode>>,
+) {
+    for (entity, camera, visible_entities, snapshot, sort_mode) in cameras.iter() {
+        if !camera.is_active {
+            continue;
+        }
+        let snapshot_entities = snapshot.and_then(ShapeSnapshotCamera::entities);
+
+        let mut visible_shapes: EntityHashSet = visible_entities
+            .iter::<With<ShaderCheckVisibility>>()
+            .filter(|entity| snapshot_entities.is_none_or(|entities| entities.contains(*entity)))
+            .copied()
+            .collect();
+        // shapes drawn with `ParamShapes` are left out of snapshots of particular entities
+        if snapshot_entities.is_none() {
+            visible_shapes.insert(immediate::IMMEDIATE_SHAPE_ENTITY);
+        }
+
+        commands.get_or_spawn(entity).insert((
+            ViewVisibleShapes(visible_shapes),
+            sort_mode.copied().unwrap_or(**default_sort_mode),
+        ));
+    }
+}
+
+/// Whether a shape should be drawn in a view
+pub(crate) fn is_visible_in_view(
+    entity: Entity,
+    layers: &RenderLayers,
+    visible_shapes: Option<&ViewVisibleShapes>,
+    view_layers: &RenderLayers,
+) -> bool {
+    view_layers.intersects(layers)
+        && visible_shapes.is_none_or(|visible_shapes| visible_shapes.0.contains(&entity))
+}
+
+/// Views which had shapes to draw this frame but where at least one of the pipelines was not yet compiled
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ViewsAwaitingPipelines {
+    pub awaiting: EntityHashSet,
+    /// Views where at least one of the pipelines failed to compile, these will never draw all their shapes
+    pub failed: EntityHashSet,
+}
+
+impl ViewsAwaitingPipelines {
+    /// Records whether the view has to wait for this pipeline before all its shapes are drawn
+    pub fn check(
+        &mut self,
+        view: Entity,
+        pipeline_cache: &PipelineCache,
+        pipeline: CachedRenderPipelineId,
+    ) {
+        match pipeline_cache
+            .pipelines()
+            .nth(pipeline.id())
+            .map(|cached| &cached.state)
+        {
+            Some(CachedPipelineState::Ok(_)) => {}
+            Some(CachedPipelineState::Err(_)) => {
+                self.failed.insert(view);
+            }
+            // pipelines queued this frame are not added to the cache until the end of the frame
+            _ => {
+                self.awaiting.insert(view);
+            }
+        }
+    }
+}
+
+/// The shapes which are visible in a view
+#[derive(Component, Clone, Debug, Default)]
+pub(crate) struct ViewVisibleShapes(pub EntityHashSet);
+
+/// A shape extracted on one of the extraction threads
+enum ExtractedShape<PARAMS: ShaderParams> {
+    Hidden(ShapeKey),
+    /// A shape which has not changed since it was last drawn
+    Unchanged(ShapeKey, ExtractedInstance),
+    Visible {
+        key: ShapeKey,
+        layers: RenderLayers,
+        image: Option<AssetId<Image>>,
+        sort: ShapeSortInputs,
+        bounds: Rect,
+        vertex: ShapeVertex<PARAMS>,
+        /// The archetype of the entity the shape was extracted from, if it can be drawn unchanged in later frames
+        archetype: Option<ArchetypeId>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct ExtractedInstance {
+    entity: Entity,
+    layers: RenderLayers,
+    /// The image sampled by the shape, if its shader uses one
+    image: Option<AssetId<Image>>,
+    /// The slot of this shape in the instance buffer
+    slot: u32,
+    sort: ShapeSortInputs,
+    bounds: Rect,
 }
 
 #[repr(C)]
@@ -475,14 +1200,479 @@ impl<PARAMS: ShaderParams> ShapeVertex<PARAMS> {
         }
     }
 
-    pub fn z_index(&self) -> f32 {
-        self.position[2]
+    /// The 2d world space bounds of the shape when drawn with this frame
+    pub fn bounds(&self, frame: Frame) -> Rect {
+        // the frame is applied before rotating, as in the vertex shader
+        let [c, s] = self.rotation.map(f32::abs);
+        let half_size = Vec2::new(
+            c * frame.half_width + s * frame.half_height,
+            s * frame.half_width + c * frame.half_height,
+        ) * self.scale;
+
+        Rect::from_center_half_size(Vec2::new(self.position[0], self.position[1]), half_size)
     }
 }
 
 unsafe impl<PARAMS: ShaderParams> NoUninit for ShapeVertex<PARAMS> {}
 
-#[derive(Component, Eq, PartialEq, Clone, Debug)]
+/// The batches of shapes drawn by each view this frame.
+/// Phase items refer to their batch using their `extra_index`.
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ViewShapeBatches {
+    views: EntityHashMap<Vec<ShapeBatch>>,
+    /// The draw functions whose phase items refer to these batches
+    draw_functions: HashSet<DrawFunctionId>,
+}
+
+impl ViewShapeBatches {
+    /// Adds a batch for the view, returning the index to use for its phase item
+    pub fn push(&mut self, view: Entity, batch: ShapeBatch) -> PhaseItemExtraIndex {
+        let batches = self.views.entry(view).or_default();
+        batches.push(batch);
+        PhaseItemExtraIndex((batches.len() - 1) as u32)
+    }
+
+    pub fn get(&self, view: Entity, extra_index: PhaseItemExtraIndex) -> Option<&ShapeBatch> {
+        self.views.get(&view)?.get(extra_index.0 as usize)
+    }
+
+    pub fn get_mut(
+        &mut self,
+        view: Entity,
+        extra_index: PhaseItemExtraIndex,
+    ) -> Option<&mut ShapeBatch> {
+        self.views.get_mut(&view)?.get_mut(extra_index.0 as usize)
+    }
+
+    /// The batch for a phase item, if it was queued by one of the shape draw functions
+    pub fn get_for_item(&self, view: Entity, item: &Transparent2d) -> Option<&ShapeBatch> {
+        if !self.draw_functions.contains(&item.draw_function) {
+            return None;
+        }
+        self.get(view, item.extra_index)
+    }
+
+    /// Whether any shapes are drawn by the view
+    pub fn has_batches(&self, view: Entity) -> bool {
+        self.views
+            .get(&view)
+            .is_some_and(|batches| !batches.is_empty())
+    }
+
+    pub fn register_draw_function(&mut self, draw_function: DrawFunctionId) {
+        self.draw_functions.insert(draw_function);
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub(crate) struct ShapeBatch {
-    range: Range<u32>,
+    pub range: Range<u32>,
+    /// Orders batches with the same sort key
+    pub order: f32,
+    /// The texture used by every shape in the batch
+    pub image: Option<AssetId<Image>>,
+    /// The 2d world space bounds of every shape in the batch
+    pub bounds: Rect,
+    /// The index of the batch in the cull buffers, if its shapes are culled on the GPU
+    pub culled: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use bevy::{
+        ecs::{
+            query::WorldQuery,
+            system::{RunSystemOnce, System, SystemParam},
+        },
+        render::MainWorld,
+        tasks::{ComputeTaskPool, TaskPool},
+    };
+
+    use super::*;
+    use crate::{
+        param_changes::ParamComponents,
+        primitives::{CircleShader, ShaderColor},
+        shader_params::ColorParams,
+        shader_uniforms::NoUniforms,
+        testing::{shape_batch, TestPhases},
+    };
+
+    /// Queues the batches for each view in the given order and joins them, returning the ranges drawn by each view
+    fn join(views: &[(Entity, Vec<Range<u32>>)]) -> EntityHashMap<Vec<(u32, u32)>> {
+        let mut phases = TestPhases::default();
+        for (view, ranges) in views {
+            for range in ranges {
+                phases.add_shapes(*view, shape_batch(range.clone(), Rect::default()));
+            }
+        }
+
+        let mut world = World::new();
+        phases.insert_into(&mut world);
+        world.run_system_once(join_adjacent_batches);
+
+        let view_batches = world.resource::<ViewShapeBatches>();
+        world
+            .resource::<ViewSortedRenderPhases<Transparent2d>>()
+            .iter()
+            .map(|(view, phase)| {
+                let mut ranges = vec![];
+                let mut index = 0;
+                while let Some(item) = phase.items.get(index) {
+                    let batch = view_batches.get(*view, item.extra_index).unwrap();
+                    ranges.push((batch.range.start, batch.range.end));
+                    index += item.batch_range.len();
+                }
+                (*view, ranges)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contiguous_batches_are_joined() {
+        let view = Entity::from_raw(0);
+        let joined = join(&[(view, vec![0..2, 2..3, 3..6])]);
+        assert_eq!(joined[&view], [(0, 6)]);
+    }
+
+    #[test]
+    fn views_with_different_orders_only_join_contiguous_ranges() {
+        let first = Entity::from_raw(0);
+        let second = Entity::from_raw(1);
+        let joined = join(&[
+            (first, vec![0..2, 2..4, 4..5]),
+            // the same instances sorted differently by the second view
+            (second, vec![2..4, 0..2, 4..5]),
+        ]);
+
+        assert_eq!(joined[&first], [(0, 5)]);
+        assert_eq!(joined[&second], [(2, 4), (0, 2), (4, 5)]);
+    }
+
+    #[test]
+    fn gaps_between_batches_are_not_joined() {
+        // shapes 2..3 are not drawn by this view
+        let view = Entity::from_raw(0);
+        let joined = join(&[(view, vec![0..2, 3..5, 5..6])]);
+        assert_eq!(joined[&view], [(0, 2), (3, 6)]);
+    }
+
+    /// A shape which is seen by a camera
+    fn visible() -> ViewVisibility {
+        let mut visibility = ViewVisibility::default();
+        visibility.set();
+        visibility
+    }
+
+    /// A visible shape with this color, drawn by the extractable
+    fn colored_shape<Extractable: ExtractToShader>(color: LinearRgba) -> impl Bundle {
+        (
+            ShaderColor::<0> { color },
+            GlobalTransform::default(),
+            visible(),
+            ShaderUsage::<Extractable>::default(),
+        )
+    }
+
+    /// Extracts the shapes of the main world once, returning the shapes which are drawn in the order of their entities
+    fn extract_once<Extractable: ExtractToShader>(
+        mut main_world: MainWorld,
+    ) -> Vec<ExtractedInstance>
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+    {
+        // shapes are extracted in parallel
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        main_world.init_resource::<ParamComponents<Extractable>>();
+
+        let mut render_world = World::new();
+        render_world.insert_resource(main_world);
+        render_world.init_resource::<ExtractedShapes<Extractable::Shader>>();
+        render_world.run_system_once(extract_shapes::<Extractable>);
+
+        let mut instances = std::mem::take(
+            &mut render_world
+                .resource_mut::<ExtractedShapes<Extractable::Shader>>()
+                .instances,
+        );
+        instances.sort_by_key(|instance| instance.entity);
+        instances
+    }
+
+    /// Draws shapes with their `ShaderColor` multiplied by their image
+    #[derive(TypePath)]
+    struct TexturedShader;
+
+    impl ParameterizedShader for TexturedShader {
+        type Params = ColorParams;
+        type Uniforms = NoUniforms;
+
+        fn fragment_body() -> impl Into<String> {
+            "return in.color * textureSample(shape_texture, shape_sampler, in.pos * 0.5 + 0.5);"
+        }
+
+        fn frame_expression() -> impl Into<String> {
+            "vec2<f32>(1.0)"
+        }
+
+        fn frame(_params: &ColorParams) -> Frame {
+            Frame::square(1.0)
+        }
+
+        fn imports() -> impl Iterator<Item = FragmentImport> {
+            [].into_iter()
+        }
+
+        const USE_TEXTURE: bool = true;
+
+        const UUID: u128 = 0x9d4e2f7a1c3b4a58b6e0d2c4f8a1b3e5;
+    }
+
+    impl ExtractToShader for TexturedShader {
+        type Shader = Self;
+        type ParamsQuery<'a> = &'a ShaderColor<0>;
+        type ParamsBundle = ShaderColor<0>;
+        type ResourceParams<'w> = ();
+        type Filter = ();
+
+        fn get_params(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            _resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        ) -> ColorParams {
+            ColorParams {
+                color: query_item.color,
+            }
+        }
+    }
+
+    /// The image extracted for a shape with an image, drawn by the extractable
+    fn extracted_image<Extractable: ExtractToShader>(
+        image: &Handle<Image>,
+    ) -> Option<AssetId<Image>>
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+    {
+        let mut main_world = MainWorld::default();
+        main_world.spawn((
+            colored_shape::<Extractable>(LinearRgba::WHITE),
+            image.clone(),
+        ));
+        extract_once::<Extractable>(main_world)[0].image
+    }
+
+    #[test]
+    fn images_are_only_extracted_for_shaders_which_use_them() {
+        let image = Handle::weak_from_u128(0x3b1f);
+        assert_eq!(extracted_image::<TexturedShader>(&image), Some(image.id()));
+        assert_eq!(extracted_image::<CircleShader>(&image), None);
+    }
+
+    /// The entities of the shapes drawn by the main world
+    fn extracted_entities<Extractable: ExtractToShader>(main_world: MainWorld) -> Vec<Entity>
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+    {
+        extract_once::<Extractable>(main_world)
+            .iter()
+            .map(|instance| instance.entity)
+            .collect()
+    }
+
+    /// Draws shapes with their `ShaderColor`, unless it is transparent
+    struct OpaqueColor;
+
+    impl ExtractToShader for OpaqueColor {
+        type Shader = CircleShader;
+        type ParamsQuery<'a> = &'a ShaderColor<0>;
+        type ParamsBundle = ShaderColor<0>;
+        type ResourceParams<'w> = ();
+        type Filter = ();
+
+        fn get_params(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            _resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        ) -> ColorParams {
+            ColorParams {
+                color: query_item.color,
+            }
+        }
+
+        fn should_draw(params: &ColorParams) -> bool {
+            params.color.alpha > 0.0
+        }
+    }
+
+    #[test]
+    fn shapes_which_should_not_be_drawn_are_not_extracted() {
+        let mut main_world = MainWorld::default();
+        let opaque = main_world
+            .spawn(colored_shape::<OpaqueColor>(LinearRgba::RED))
+            .id();
+        main_world.spawn(colored_shape::<OpaqueColor>(LinearRgba::NONE));
+
+        assert_eq!(extracted_entities::<OpaqueColor>(main_world), [opaque]);
+    }
+
+    #[derive(Component)]
+    struct Marked;
+
+    /// Draws shapes with their `ShaderColor`, if they are `Marked`
+    struct MarkedColor;
+
+    impl ExtractToShader for MarkedColor {
+        type Shader = CircleShader;
+        type ParamsQuery<'a> = &'a ShaderColor<0>;
+        type ParamsBundle = ShaderColor<0>;
+        type ResourceParams<'w> = ();
+        type Filter = With<Marked>;
+
+        fn get_params(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            _resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        ) -> ColorParams {
+            ColorParams {
+                color: query_item.color,
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_excluded_by_the_filter_are_not_extracted() {
+        let mut main_world = MainWorld::default();
+        let marked = main_world
+            .spawn((colored_shape::<MarkedColor>(LinearRgba::RED), Marked))
+            .id();
+        main_world.spawn(colored_shape::<MarkedColor>(LinearRgba::RED));
+
+        assert_eq!(extracted_entities::<MarkedColor>(main_world), [marked]);
+    }
+
+    /// The entity and x position each `PositionedColor` shape was extracted with
+    static EXTRACTED_CONTEXTS: Mutex<Vec<(Entity, f32)>> = Mutex::new(vec![]);
+
+    /// Draws shapes with their `ShaderColor`, recording the context they were extracted with
+    struct PositionedColor;
+
+    impl ExtractToShader for PositionedColor {
+        type Shader = CircleShader;
+        type ParamsQuery<'a> = &'a ShaderColor<0>;
+        type ParamsBundle = ShaderColor<0>;
+        type ResourceParams<'w> = ();
+        type Filter = ();
+
+        fn get_params(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            _resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        ) -> ColorParams {
+            ColorParams {
+                color: query_item.color,
+            }
+        }
+
+        fn get_params_with_context(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+            context: &ExtractionContext,
+        ) -> ColorParams {
+            EXTRACTED_CONTEXTS
+                .lock()
+                .unwrap()
+                .push((context.entity, context.transform.translation().x));
+            Self::get_params(query_item, resource)
+        }
+    }
+
+    #[test]
+    fn params_are_extracted_with_the_entity_and_its_transform() {
+        let mut main_world = MainWorld::default();
+        let entity = main_world
+            .spawn(colored_shape::<PositionedColor>(LinearRgba::RED))
+            .insert(GlobalTransform::from_xyz(5.0, 0.0, 0.0))
+            .id();
+
+        assert_eq!(extracted_entities::<PositionedColor>(main_world), [entity]);
+        assert_eq!(*EXTRACTED_CONTEXTS.lock().unwrap(), [(entity, 5.0)]);
+    }
+
+    /// The colors extracted by `OptionalColor`
+    static EXTRACTED_COLORS: Mutex<Vec<LinearRgba>> = Mutex::new(vec![]);
+
+    /// Draws shapes with their `ShaderColor`, or white if they have none
+    struct OptionalColor;
+
+    impl ExtractToShader for OptionalColor {
+        type Shader = CircleShader;
+        type ParamsQuery<'a> = Option<&'a ShaderColor<0>>;
+        type ParamsBundle = ();
+        type ResourceParams<'w> = ();
+        type Filter = ();
+
+        fn get_params(
+            query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+            _resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        ) -> ColorParams {
+            let color = query_item.map_or(LinearRgba::WHITE, |color| color.color);
+            EXTRACTED_COLORS.lock().unwrap().push(color);
+            ColorParams { color }
+        }
+    }
+
+    #[test]
+    fn shapes_are_extracted_again_when_components_are_removed() {
+        // shapes are extracted in parallel
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let mut main_world = MainWorld::default();
+        main_world.init_resource::<ParamComponents<OptionalColor>>();
+        let entity = main_world
+            .spawn((
+                ShaderColor::<0> {
+                    color: LinearRgba::RED,
+                },
+                ShaderZIndex::new(1, 0.0),
+                GlobalTransform::default(),
+                visible(),
+                ShaderUsage::<OptionalColor>::default(),
+            ))
+            .id();
+
+        let mut render_world = World::new();
+        render_world.insert_resource(main_world);
+        render_world.init_resource::<ExtractedShapes<CircleShader>>();
+        let mut system = IntoSystem::into_system(extract_shapes::<OptionalColor>);
+        system.initialize(&mut render_world);
+
+        // extracts the shapes as one frame would, returning the depth of each shape drawn
+        let mut extract = |render_world: &mut World| -> Vec<f32> {
+            system.run((), render_world);
+            let mut shapes = render_world.resource_mut::<ExtractedShapes<CircleShader>>();
+            shapes.instance_buffer.remove_unseen();
+            let depths = std::mem::take(&mut shapes.instances)
+                .iter()
+                .map(|instance| instance.sort.key(ParamShaderSortMode::Z).depth)
+                .collect();
+            render_world
+                .resource_mut::<MainWorld>()
+                .increment_change_tick();
+            depths
+        };
+
+        assert_eq!(extract(&mut render_world), [1.0]);
+        assert_eq!(*EXTRACTED_COLORS.lock().unwrap(), [LinearRgba::RED]);
+
+        // unchanged shapes are drawn without extracting their params
+        assert_eq!(extract(&mut render_world), [1.0]);
+        assert_eq!(*EXTRACTED_COLORS.lock().unwrap(), [LinearRgba::RED]);
+
+        render_world
+            .resource_mut::<MainWorld>()
+            .entity_mut(entity)
+            .remove::<(ShaderColor<0>, ShaderZIndex)>();
+        assert_eq!(extract(&mut render_world), [0.0]);
+        assert_eq!(
+            *EXTRACTED_COLORS.lock().unwrap(),
+            [LinearRgba::RED, LinearRgba::WHITE]
+        );
+    }
 }
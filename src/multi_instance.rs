@@ -0,0 +1,325 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        view::{RenderLayers, VisibilitySystems},
+        Extract, ExtractSchedule, RenderApp,
+    },
+    utils::Parallel,
+};
+
+use crate::{
+    bounds::instance_corners, bundle::ShaderCheckVisibility, instance_buffer::ShapeKey,
+    parameterized_shader::ParameterizedShader, registry, registry::ParamShaderRegistry,
+    sorting::ShapeSortInputs, ExtractedShape, ExtractedShapes, ParamShaderPlugin,
+    ShaderYSortOffset, ShaderZIndex, ShapeCulling, ShapeVertex,
+};
+
+/// Many shapes drawn from a single entity, such as the stars of a star field or the points of a chart.
+///
+/// Each instance is drawn with its own params and its transform relative to the entity.
+/// Instances with equal sort keys are drawn in the order of the list, later instances on top.
+#[derive(Component)]
+pub struct MultiShapeInstances<Shader: ParameterizedShader> {
+    pub instances: Vec<ShapeInstance<Shader::Params>>,
+}
+
+impl<Shader: ParameterizedShader> std::fmt::Debug for MultiShapeInstances<Shader> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiShapeInstances")
+            .field("instances", &self.instances)
+            .finish()
+    }
+}
+
+impl<Shader: ParameterizedShader> Clone for MultiShapeInstances<Shader> {
+    fn clone(&self) -> Self {
+        Self {
+            instances: self.instances.clone(),
+        }
+    }
+}
+
+impl<Shader: ParameterizedShader> Default for MultiShapeInstances<Shader> {
+    fn default() -> Self {
+        Self {
+            instances: Default::default(),
+        }
+    }
+}
+
+impl<Shader: ParameterizedShader> FromIterator<ShapeInstance<Shader::Params>>
+    for MultiShapeInstances<Shader>
+{
+    fn from_iter<T: IntoIterator<Item = ShapeInstance<Shader::Params>>>(iter: T) -> Self {
+        Self {
+            instances: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// One of the shapes drawn by [`MultiShapeInstances`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ShapeInstance<Params> {
+    /// The transform of the shape relative to its entity
+    pub transform: Transform,
+    pub params: Params,
+}
+
+impl<Params> ShapeInstance<Params> {
+    pub fn new(transform: Transform, params: Params) -> Self {
+        Self { transform, params }
+    }
+}
+
+#[derive(Bundle)]
+pub struct MultiShapeBundle<Shader: ParameterizedShader> {
+    pub instances: MultiShapeInstances<Shader>,
+    /// The transform that every instance is relative to
+    pub transform: Transform,
+    /// Indicates that the shader visibility should be checked
+    pub shader_check_visibility: ShaderCheckVisibility,
+    pub global_transform: GlobalTransform,
+    pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
+}
+
+impl<Shader: ParameterizedShader> Default for MultiShapeBundle<Shader> {
+    fn default() -> Self {
+        Self {
+            instances: Default::default(),
+            transform: Default::default(),
+            shader_check_visibility: ShaderCheckVisibility,
+            global_transform: Default::default(),
+            visibility: Default::default(),
+            inherited_visibility: Default::default(),
+            view_visibility: Default::default(),
+        }
+    }
+}
+
+/// Draws the [`MultiShapeInstances`] of a shader
+pub struct MultiShapeInstancesPlugin<Shader: ParameterizedShader>(PhantomData<Shader>);
+
+impl<Shader: ParameterizedShader> Default for MultiShapeInstancesPlugin<Shader> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<Shader: ParameterizedShader> Plugin for MultiShapeInstancesPlugin<Shader> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<ParamShaderPlugin<Shader>>() {
+            app.add_plugins(ParamShaderPlugin::<Shader>::default());
+        }
+
+        app.world_mut()
+            .resource_mut::<ParamShaderRegistry>()
+            .mark_multi_instances_added::<Shader>();
+        add_multi_instance_systems::<Shader>(app.world_mut());
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            add_multi_instance_extract_systems::<Shader>(render_app.world_mut());
+        };
+    }
+}
+
+/// Adds the main world systems of the multi-instances of a shader, which only run while they are registered
+pub(crate) fn add_multi_instance_systems<Shader: ParameterizedShader>(world: &mut World) {
+    world.resource_mut::<Schedules>().add_systems(
+        PostUpdate,
+        update_multi_shape_aabbs::<Shader>
+            .in_set(VisibilitySystems::CalculateBounds)
+            .run_if(registry::are_multi_instances_registered::<Shader>),
+    );
+}
+
+/// Adds the render world systems which extract the multi-instances of a shader while they are registered
+pub(crate) fn add_multi_instance_extract_systems<Shader: ParameterizedShader>(
+    render_world: &mut World,
+) {
+    render_world.resource_mut::<Schedules>().add_systems(
+        ExtractSchedule,
+        extract_multi_shape_instances::<Shader>
+            .run_if(registry::are_multi_instances_registered_in_main_world::<Shader>),
+    );
+}
+
+/// The world space transform of an instance
+fn instance_transform<Params>(
+    entity_transform: &GlobalTransform,
+    instance: &ShapeInstance<Params>,
+) -> GlobalTransform {
+    entity_transform.mul_transform(instance.transform)
+}
+
+/// Keeps the bounds of each entity enclosing all of its instances, so they can be frustum culled together.
+///
+/// Bounds are only recalculated for entities whose instances or transform have changed.
+fn update_multi_shape_aabbs<Shader: ParameterizedShader>(
+    mut commands: Commands,
+    culling: Res<ShapeCulling>,
+    mut shapes: Query<(
+        Entity,
+        Ref<MultiShapeInstances<Shader>>,
+        Ref<GlobalTransform>,
+        Option<&mut Aabb>,
+    )>,
+) {
+    for (entity, shapes, transform, aabb) in shapes.iter_mut() {
+        if *culling == ShapeCulling::Gpu {
+            if aabb.is_some() {
+                commands.entity(entity).remove::<Aabb>();
+            }
+            continue;
+        }
+        if !culling.is_changed()
+            && aabb.is_some()
+            && !shapes.is_changed()
+            && !transform.is_changed()
+        {
+            continue;
+        }
+
+        let corners = shapes.instances.iter().flat_map(|instance| {
+            instance_corners(
+                Shader::frame(&instance.params),
+                &transform,
+                &instance_transform(&transform, instance),
+            )
+        });
+        let new_aabb = Aabb::enclosing(corners).unwrap_or_default();
+
+        match aabb {
+            Some(mut aabb) => {
+                // avoid triggering change detection when nothing has changed
+                aabb.set_if_neq(new_aabb);
+            }
+            None => {
+                commands.entity(entity).insert(new_aabb);
+            }
+        }
+    }
+}
+
+fn extract_multi_shape_instances<Shader: ParameterizedShader>(
+    mut extracted_shapes: ResMut<ExtractedShapes<Shader>>,
+    shape_query: Extract<
+        Query<(
+            Entity,
+            &ViewVisibility,
+            &MultiShapeInstances<Shader>,
+            &GlobalTransform,
+            Option<&RenderLayers>,
+            Option<&ShaderZIndex>,
+            Option<&ShaderYSortOffset>,
+            Option<&Handle<Image>>,
+        )>,
+    >,
+    mut thread_queues: Local<Parallel<Vec<ExtractedShape<Shader::Params>>>>,
+) {
+    let extractor = TypeId::of::<MultiShapeInstances<Shader>>();
+
+    shape_query.par_iter().for_each(
+        |(entity, view_visibility, shapes, transform, layers, z_index, y_offset, image)| {
+            let layers = layers.cloned().unwrap_or_default();
+            let image = image.filter(|_| Shader::USE_TEXTURE).map(Handle::id);
+
+            thread_queues.scope(|queue| {
+                for (index, instance) in shapes.instances.iter().enumerate() {
+                    let key = ShapeKey {
+                        entity,
+                        extractor,
+                        index: index as u32,
+                    };
+
+                    // hidden entities keep the slots of their instances
+                    if !view_visibility.get() {
+                        queue.push(ExtractedShape::Hidden(key));
+                        continue;
+                    }
+
+                    let instance_transform = instance_transform(transform, instance);
+                    let vertex = ShapeVertex::new(&instance_transform, instance.params);
+
+                    queue.push(ExtractedShape::Visible {
+                        key,
+                        layers: layers.clone(),
+                        image,
+                        sort: ShapeSortInputs::new(&instance_transform, z_index, y_offset),
+                        bounds: vertex.bounds(Shader::frame(&instance.params)),
+                        vertex,
+                        archetype: None,
+                    });
+                }
+            });
+        },
+    );
+
+    // slots are assigned on a single thread
+    for queue in thread_queues.iter_mut() {
+        for shape in queue.drain(..) {
+            extracted_shapes.add(shape);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3A;
+
+    use super::*;
+    use crate::{primitives::CircleShader, shader_params::ColorParams};
+
+    #[test]
+    fn aabbs_are_only_updated_for_changed_registered_instances() {
+        let mut world = World::new();
+        world.init_resource::<Schedules>();
+        world.init_resource::<ShapeCulling>();
+        world.init_resource::<ParamShaderRegistry>();
+        world
+            .resource_mut::<ParamShaderRegistry>()
+            .mark_multi_instances_added::<CircleShader>();
+        add_multi_instance_systems::<CircleShader>(&mut world);
+
+        let instance =
+            |x: f32| ShapeInstance::new(Transform::from_xyz(x, 0.0, 0.0), ColorParams::default());
+        let entity = world
+            .spawn((
+                MultiShapeInstances::<CircleShader>::from_iter([instance(2.0)]),
+                GlobalTransform::default(),
+            ))
+            .id();
+        let update = |world: &mut World| {
+            world.run_schedule(PostUpdate);
+            *world.get::<Aabb>(entity).unwrap()
+        };
+
+        assert_eq!(update(&mut world).center, Vec3A::new(2.0, 0.0, 0.0));
+
+        // unchanged entities keep their bounds
+        let marker = Aabb::from_min_max(Vec3::ZERO, Vec3::ONE);
+        *world.get_mut::<Aabb>(entity).unwrap() = marker;
+        assert_eq!(update(&mut world), marker);
+
+        let mut instances = world
+            .get_mut::<MultiShapeInstances<CircleShader>>(entity)
+            .unwrap();
+        instances.instances.push(instance(-2.0));
+        assert_eq!(update(&mut world).center, Vec3A::ZERO);
+
+        // the instances of unregistered shaders are left alone
+        world
+            .resource_mut::<ParamShaderRegistry>()
+            .unregister_multi_instances::<CircleShader>();
+        *world.get_mut::<Aabb>(entity).unwrap() = marker;
+        let mut instances = world
+            .get_mut::<MultiShapeInstances<CircleShader>>(entity)
+            .unwrap();
+        instances.instances.clear();
+        assert_eq!(update(&mut world), marker);
+    }
+}
@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::{
+        component::ComponentId,
+        system::{StaticSystemParam, SystemChangeTick, SystemParam},
+        world::EntityRef,
+    },
+    prelude::*,
+};
+
+use crate::{
+    cache::ShaderCacheToTexture, parameterized_shader::ExtractToShader, ShaderYSortOffset,
+    ShaderZIndex,
+};
+
+/// The components the params of an extractable are read from, so that shapes are only extracted again when one of them changes
+#[derive(Resource)]
+pub(crate) struct ParamComponents<Extractable: ExtractToShader> {
+    /// `None` if the params can change without any of the shape's components changing, such as when they are read from `ResourceParams`
+    components: Option<Vec<ComponentId>>,
+    phantom: PhantomData<Extractable>,
+}
+
+impl<Extractable: ExtractToShader> FromWorld for ParamComponents<Extractable> {
+    fn from_world(world: &mut World) -> Self {
+        let state = QueryState::<Extractable::ParamsQuery<'static>>::new(world);
+        let access = state.component_access().access();
+
+        let components = if reads_anything::<Extractable::ResourceParams<'static>>(world)
+            || access.has_read_all()
+        {
+            None
+        } else {
+            let mut components: Vec<ComponentId> = access.reads().collect();
+            // these change how a shape is drawn without changing its params
+            components.extend([
+                world.init_component::<GlobalTransform>(),
+                world.init_component::<ShaderZIndex>(),
+                world.init_component::<ShaderYSortOffset>(),
+                world.init_component::<ShaderCacheToTexture>(),
+            ]);
+            Some(components)
+        };
+
+        Self {
+            components,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<Extractable: ExtractToShader> ParamComponents<Extractable> {
+    /// Whether the params of the entity may have changed since the system last ran.
+    /// Components which were added count as changed. Removed components are noticed by the caller comparing the entity's archetype with the one it had then
+    pub fn changed(&self, entity: &EntityRef, ticks: &SystemChangeTick) -> bool {
+        let Some(components) = &self.components else {
+            return true;
+        };
+
+        components.iter().any(|component| {
+            entity
+                .get_change_ticks_by_id(*component)
+                .is_some_and(|component_ticks| {
+                    component_ticks.is_changed(ticks.last_run(), ticks.this_run())
+                })
+        })
+    }
+}
+
+/// Whether the system param reads any resources or components
+fn reads_anything<Param: SystemParam + 'static>(world: &mut World) -> bool {
+    fn read<Param: SystemParam + 'static>(_: StaticSystemParam<Param>) {}
+
+    let mut system = IntoSystem::into_system(read::<Param>);
+    system.initialize(world);
+    let access = system.component_access();
+    access.has_read_all() || access.reads().next().is_some()
+}
@@ -1,12 +1,13 @@
 use std::fmt::Debug;
 
-use crate::shader_params::ShaderParams;
+use crate::{frame::Frame, shader_params::ShaderParams, shader_uniforms::ShaderUniforms};
 use bevy::{
     ecs::{
         bundle::Bundle,
-        query::{ReadOnlyQueryData, WorldQuery},
+        query::{QueryFilter, ReadOnlyQueryData, WorldQuery},
         system::{ReadOnlySystemParam, SystemParam},
     },
+    prelude::{Entity, GlobalTransform},
     reflect::TypePath,
 };
 
@@ -15,31 +16,101 @@ pub trait ExtractToShader: Sync + Send + 'static {
     type Shader: ParameterizedShader;
     type ParamsQuery<'a>: ReadOnlyQueryData;
     type ParamsBundle: Bundle;
+    /// Shapes whose params read anything through this are extracted every frame,
+    /// while others are only extracted when their components or transform change
     type ResourceParams<'w>: SystemParam + ReadOnlySystemParam;
+    /// Only entities matching this filter are drawn, use `()` to draw every entity with a `ShaderUsage` of this type
+    type Filter: QueryFilter;
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
-        resource: & <Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
     ) -> <Self::Shader as ParameterizedShader>::Params;
+
+    /// Get the params of a shape, given the entity and transform they are being extracted for.
+    /// This is what is called when extracting shapes, and by default it calls `get_params`.
+    /// Override it for params which depend on the entity or its transform
+    fn get_params_with_context(
+        query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+        _context: &ExtractionContext,
+    ) -> <Self::Shader as ParameterizedShader>::Params {
+        Self::get_params(query_item, resource)
+    }
+
+    /// Panics if shapes cannot be extracted with this, such as when it is misconfigured.
+    /// This is called once, when the extractable is added to the app or registered
+    fn validate() {}
+
+    /// Whether a shape with these params should be drawn.
+    /// Shapes which would not be visible, such as those with no width or a transparent color, can return false so that they are not sent to the GPU
+    fn should_draw(_params: &<Self::Shader as ParameterizedShader>::Params) -> bool {
+        true
+    }
+
+    /// The transform the shape is drawn with, by default the transform of its entity.
+    /// Shapes which are naturally described in other terms, such as a line segment between two points, can derive their position, rotation and scale from their params instead.
+    /// Only the translation, the rotation around the z axis and the scale along the x axis are used.
+    fn instance_transform(
+        _params: &<Self::Shader as ParameterizedShader>::Params,
+        context: &ExtractionContext,
+    ) -> GlobalTransform {
+        *context.transform
+    }
+}
+
+/// The entity a shape's params are being extracted for
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractionContext<'a> {
+    pub entity: Entity,
+    pub transform: &'a GlobalTransform,
 }
 
 /// A particular shader
 pub trait ParameterizedShader: Sync + Send + TypePath + 'static {
     type Params: ShaderParams;
+    /// Values shared by every shape drawn with this shader, use `NoUniforms` if there are none
+    type Uniforms: ShaderUniforms;
 
     /// Get the body of the fragment shader fragment function
     /// This will take an `in` argument with a `pos` parameter and one parameter for each field
+    /// The fields of the uniforms can be read from `uniforms`
     /// It should return `vec4<f32>` representing the color of the pixel
     fn fragment_body() -> impl Into<String>;
 
     /// An expression that returns a `vec2<f32>` representing the half-width and half-height of the  frame
     fn frame_expression() -> impl Into<String>;
 
+    /// The frame of a shape with these params, this should match `frame_expression`
+    /// This is used to calculate the bounds of the shape for frustum culling
+    fn frame(params: &Self::Params) -> Frame;
+
     /// Get imports
     fn imports() -> impl Iterator<Item = FragmentImport>;
 
+    /// Functions and types which can be used by `frame_expression`, these are placed in the vertex shader
+    fn vertex_functions() -> impl Into<String> {
+        ""
+    }
+
+    /// Functions and types which can be used by `fragment_body`, these are placed in the fragment shader
+    fn fragment_functions() -> impl Into<String> {
+        ""
+    }
+
     const USE_TIME: bool = false;
 
+    /// Whether the shader samples an image.
+    /// The image is taken from the `Handle<Image>` component of each shape, and shapes without one use a white image.
+    /// The fragment body can read it with `textureSample(shape_texture, shape_sampler, uv)`
+    const USE_TEXTURE: bool = false;
+
+    /// The asset path of a `.body.wgsl` file containing the fragment body, to use instead of `fragment_body`.
+    /// `fragment_body` is used until the file has loaded, and the fragment shader is regenerated whenever the file changes,
+    /// so the body can be changed without recompiling when the `file_watcher` feature is enabled.
+    /// This is not used when the shader is part of a `CombinedShader`
+    const FRAGMENT_BODY_PATH: Option<&'static str> = None;
+
     const UUID: u128; //TODO prevent duplicates
 }
 
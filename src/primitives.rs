@@ -27,6 +27,7 @@ pub struct RectShader;
 
 impl ParameterizedShader for RectShader {
     type Params = RectShaderParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -39,6 +40,10 @@ impl ParameterizedShader for RectShader {
         "vec2<f32>(max(vertex.width, vertex.height))"
     }
 
+    fn frame(params: &Self::Params) -> Frame {
+        Frame::square(params.width.max(params.height))
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [imports::fill::SIMPLE_FILL, imports::sdf::RECT].into_iter()
     }
@@ -67,6 +72,7 @@ impl ExtractToShader for RectShaderExtraction {
     type ParamsQuery<'a> = (&'a ShaderColor<0>, &'a ShaderProportions);
     type ParamsBundle = (ShaderColor<0>, ShaderProportions);
     type ResourceParams<'w> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -85,6 +91,7 @@ pub struct RoundedRectShader;
 
 impl ParameterizedShader for RoundedRectShader {
     type Params = RoundedRectShaderParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -97,6 +104,10 @@ impl ParameterizedShader for RoundedRectShader {
         "vec2<f32>(max(vertex.width, vertex.height))"
     }
 
+    fn frame(params: &Self::Params) -> Frame {
+        Frame::square(params.width.max(params.height))
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [imports::fill::SIMPLE_FILL, imports::sdf::ROUNDED_RECT].into_iter()
     }
@@ -116,6 +127,7 @@ impl ExtractToShader for RoundedRectShaderExtraction {
     );
     type ParamsBundle = (ShaderColor<0>, ShaderRounding, ShaderProportions);
     type ResourceParams<'w> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -235,6 +247,7 @@ impl ExtractToShader for RoundedRectWithBorderShader {
         ShaderBorder,
     );
     type ResourceParams<'w> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -254,6 +267,7 @@ impl ExtractToShader for RoundedRectWithBorderShader {
 
 impl ParameterizedShader for RoundedRectWithBorderShader {
     type Params = RoundedRectWithBorderShaderParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -266,6 +280,10 @@ impl ParameterizedShader for RoundedRectWithBorderShader {
         "vec2<f32>(max(vertex.width, vertex.height))"
     }
 
+    fn frame(params: &Self::Params) -> Frame {
+        Frame::square(params.width.max(params.height))
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [imports::fill::FILL_WITH_OUTLINE, imports::sdf::ROUNDED_RECT].into_iter()
     }
@@ -316,6 +334,7 @@ impl ExtractToShader for CircleShader {
     type ParamsQuery<'a> = &'a ShaderColor<0>;
     type ParamsBundle = ShaderColor<0>;
     type ResourceParams<'w> = ();
+    type Filter = ();
 
     fn get_params(
         query_item: <Self::ParamsQuery<'_> as bevy::ecs::query::WorldQuery>::Item<'_>,
@@ -329,6 +348,7 @@ impl ExtractToShader for CircleShader {
 
 impl ParameterizedShader for CircleShader {
     type Params = ColorParams;
+    type Uniforms = NoUniforms;
 
     fn fragment_body() -> impl Into<String> {
         SDFColorCall {
@@ -341,6 +361,10 @@ impl ParameterizedShader for CircleShader {
         Frame::square(1.0)
     }
 
+    fn frame(_params: &Self::Params) -> Frame {
+        Frame::square(1.0)
+    }
+
     fn imports() -> impl Iterator<Item = FragmentImport> {
         [imports::fill::SIMPLE_FILL, imports::sdf::CIRCLE].into_iter()
     }
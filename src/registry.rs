@@ -0,0 +1,291 @@
+use std::any::TypeId;
+
+use bevy::{
+    ecs::system::SystemParamItem,
+    prelude::*,
+    render::{Extract, MainWorld},
+    utils::HashSet,
+};
+
+use crate::{
+    add_extractable_extract_systems, add_extractable_systems, add_shader_extract_systems,
+    add_shader_render_systems, add_shader_systems, init_shader_pipelines,
+    multi_instance::{self, MultiShapeInstances},
+    parameterized_shader::{ExtractToShader, ParameterizedShader},
+    ParameterShadersPlugin,
+};
+
+/// The shaders whose shapes are drawn.
+///
+/// Shaders added with an `ExtractToShaderPlugin` or a `MultiShapeInstancesPlugin` are registered when the app is built,
+/// other shaders can be registered and unregistered while the app is running, e.g. with [`ParamShaderCommandsExt`].
+/// The systems and pipelines of a shader are set up the first time it is registered, at the end of the frame.
+/// Bevy cannot remove systems, so unregistering a shader stops its shapes being extracted and drawn, but its systems remain.
+#[derive(Resource, Default)]
+pub struct ParamShaderRegistry {
+    /// The extractables whose shapes are drawn, including the `MultiShapeInstances` of each shader
+    registered: HashSet<TypeId>,
+    /// The extractables whose systems have been added, including the `MultiShapeInstances` of each shader
+    added: HashSet<TypeId>,
+    /// The shaders whose systems have been added, an extractable may also be its own shader
+    added_shaders: HashSet<TypeId>,
+    /// Setup waiting to be run in the main world
+    pending_main: Vec<fn(&mut World)>,
+    /// Setup waiting to be run in the render world during extraction
+    pending_render: Vec<fn(&mut World)>,
+    /// Extraction systems waiting to be added, which cannot happen while extracting
+    pending_extract: Vec<fn(&mut World)>,
+}
+
+impl ParamShaderRegistry {
+    /// Whether the shapes of this extractable are drawn
+    pub fn is_registered<Extractable: ExtractToShader>(&self) -> bool {
+        self.registered.contains(&TypeId::of::<Extractable>())
+    }
+
+    /// Draws the shapes of this extractable, setting up its shader if it has not been used yet
+    pub fn register<Extractable: ExtractToShader>(&mut self)
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+    {
+        if !self.added.contains(&TypeId::of::<Extractable>()) {
+            Extractable::validate();
+        }
+
+        self.registered.insert(TypeId::of::<Extractable>());
+        self.add_shader::<Extractable::Shader>();
+
+        if self.added.insert(TypeId::of::<Extractable>()) {
+            self.pending_main
+                .push(add_extractable_systems::<Extractable>);
+            self.pending_extract
+                .push(add_extractable_extract_systems::<Extractable>);
+        }
+    }
+
+    /// Stops drawing the shapes of this extractable
+    pub fn unregister<Extractable: ExtractToShader>(&mut self) {
+        self.registered.remove(&TypeId::of::<Extractable>());
+    }
+
+    /// Whether the [`MultiShapeInstances`] of this shader are drawn
+    pub fn is_multi_instances_registered<Shader: ParameterizedShader>(&self) -> bool {
+        self.registered
+            .contains(&TypeId::of::<MultiShapeInstances<Shader>>())
+    }
+
+    /// Draws the [`MultiShapeInstances`] of this shader, setting up the shader if it has not been used yet
+    pub fn register_multi_instances<Shader: ParameterizedShader>(&mut self) {
+        let id = TypeId::of::<MultiShapeInstances<Shader>>();
+        self.registered.insert(id);
+        self.add_shader::<Shader>();
+
+        if self.added.insert(id) {
+            self.pending_main
+                .push(multi_instance::add_multi_instance_systems::<Shader>);
+            self.pending_extract
+                .push(multi_instance::add_multi_instance_extract_systems::<Shader>);
+        }
+    }
+
+    /// Stops drawing the [`MultiShapeInstances`] of this shader
+    pub fn unregister_multi_instances<Shader: ParameterizedShader>(&mut self) {
+        self.registered
+            .remove(&TypeId::of::<MultiShapeInstances<Shader>>());
+    }
+
+    /// Sets up the shader at the end of the frame if it has not been used yet
+    fn add_shader<Shader: ParameterizedShader>(&mut self) {
+        if self.added_shaders.insert(TypeId::of::<Shader>()) {
+            self.pending_main.push(add_shader_systems::<Shader>);
+            self.pending_render.push(setup_shader_render::<Shader>);
+            self.pending_extract
+                .push(add_shader_extract_systems::<Shader>);
+        }
+    }
+
+    /// Records an extractable whose systems were added when the app was built
+    pub(crate) fn mark_added<Extractable: ExtractToShader>(&mut self) {
+        self.registered.insert(TypeId::of::<Extractable>());
+        self.added.insert(TypeId::of::<Extractable>());
+    }
+
+    /// Records multi-instances whose systems were added when the app was built
+    pub(crate) fn mark_multi_instances_added<Shader: ParameterizedShader>(&mut self) {
+        let id = TypeId::of::<MultiShapeInstances<Shader>>();
+        self.registered.insert(id);
+        self.added.insert(id);
+    }
+
+    /// Records a shader whose systems were added when the app was built
+    pub(crate) fn mark_shader_added<Shader: 'static>(&mut self) {
+        self.added_shaders.insert(TypeId::of::<Shader>());
+    }
+}
+
+fn setup_shader_render<Shader: ParameterizedShader>(render_world: &mut World) {
+    add_shader_render_systems::<Shader>(render_world);
+    init_shader_pipelines::<Shader>(render_world);
+}
+
+/// Registers and unregisters shaders while the app is running
+pub trait ParamShaderCommandsExt {
+    /// Draws the shapes of this extractable from the end of this frame, see [`ParamShaderRegistry::register`]
+    fn register_param_shader<Extractable: ExtractToShader>(&mut self)
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync;
+
+    /// Stops drawing the shapes of this extractable
+    fn unregister_param_shader<Extractable: ExtractToShader>(&mut self);
+
+    /// Draws the [`MultiShapeInstances`] of this shader from the end of this frame, see [`ParamShaderRegistry::register_multi_instances`]
+    fn register_multi_instances<Shader: ParameterizedShader>(&mut self);
+
+    /// Stops drawing the [`MultiShapeInstances`] of this shader
+    fn unregister_multi_instances<Shader: ParameterizedShader>(&mut self);
+}
+
+impl ParamShaderCommandsExt for Commands<'_, '_> {
+    fn register_param_shader<Extractable: ExtractToShader>(&mut self)
+    where
+        for<'w, 'a, 'b> SystemParamItem<'a, 'b, Extractable::ResourceParams<'w>>: Sync,
+    {
+        self.add(|world: &mut World| {
+            world
+                .resource_mut::<ParamShaderRegistry>()
+                .register::<Extractable>();
+        });
+    }
+
+    fn unregister_param_shader<Extractable: ExtractToShader>(&mut self) {
+        self.add(|world: &mut World| {
+            world
+                .resource_mut::<ParamShaderRegistry>()
+                .unregister::<Extractable>();
+        });
+    }
+
+    fn register_multi_instances<Shader: ParameterizedShader>(&mut self) {
+        self.add(|world: &mut World| {
+            world
+                .resource_mut::<ParamShaderRegistry>()
+                .register_multi_instances::<Shader>();
+        });
+    }
+
+    fn unregister_multi_instances<Shader: ParameterizedShader>(&mut self) {
+        self.add(|world: &mut World| {
+            world
+                .resource_mut::<ParamShaderRegistry>()
+                .unregister_multi_instances::<Shader>();
+        });
+    }
+}
+
+/// Adds the [`ParamShaderRegistry`] to an app which only registers shaders while it is running
+#[derive(Debug, Default)]
+pub struct ParamShaderRegistryPlugin;
+
+impl Plugin for ParamShaderRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<ParameterShadersPlugin>() {
+            app.add_plugins(ParameterShadersPlugin);
+        }
+    }
+}
+
+pub(crate) fn is_registered<Extractable: ExtractToShader>(
+    registry: Res<ParamShaderRegistry>,
+) -> bool {
+    registry.is_registered::<Extractable>()
+}
+
+pub(crate) fn is_registered_in_main_world<Extractable: ExtractToShader>(
+    registry: Extract<Res<ParamShaderRegistry>>,
+) -> bool {
+    registry.is_registered::<Extractable>()
+}
+
+pub(crate) fn are_multi_instances_registered<Shader: ParameterizedShader>(
+    registry: Res<ParamShaderRegistry>,
+) -> bool {
+    registry.is_multi_instances_registered::<Shader>()
+}
+
+pub(crate) fn are_multi_instances_registered_in_main_world<Shader: ParameterizedShader>(
+    registry: Extract<Res<ParamShaderRegistry>>,
+) -> bool {
+    registry.is_multi_instances_registered::<Shader>()
+}
+
+/// Runs the main world setup of newly registered shaders.
+/// This happens in `Last`, as schedules cannot be changed while they are running
+pub(crate) fn setup_registered_shaders(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<ParamShaderRegistry>().pending_main);
+    for setup in pending {
+        setup(world);
+    }
+}
+
+/// The extraction systems of newly registered shaders, which are added once extraction has finished
+#[derive(Resource, Default)]
+pub(crate) struct PendingExtractSystems(Vec<fn(&mut World)>);
+
+/// Runs the render world setup of newly registered shaders
+pub(crate) fn setup_registered_shaders_render(world: &mut World) {
+    let (pending_render, pending_extract) = {
+        let mut main_world = world.resource_mut::<MainWorld>();
+        let mut registry = main_world.resource_mut::<ParamShaderRegistry>();
+        (
+            std::mem::take(&mut registry.pending_render),
+            std::mem::take(&mut registry.pending_extract),
+        )
+    };
+
+    for setup in pending_render {
+        setup(world);
+    }
+    world
+        .resource_mut::<PendingExtractSystems>()
+        .0
+        .extend(pending_extract);
+}
+
+/// Adds the extraction systems of newly registered shaders, which will run from the next frame
+pub(crate) fn add_pending_extract_systems(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<PendingExtractSystems>().0);
+    for setup in pending {
+        setup(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        primitives::{RectShader, RectShaderExtraction},
+        style::StyledShape,
+    };
+
+    #[test]
+    fn shaders_are_only_set_up_the_first_time_they_are_registered() {
+        let mut registry = ParamShaderRegistry::default();
+
+        registry.register::<RectShaderExtraction>();
+        assert!(registry.is_registered::<RectShaderExtraction>());
+        // the systems of the extractable and of its shader
+        assert_eq!(registry.pending_main.len(), 2);
+
+        // another extractable of the same shader only adds its own systems
+        registry.register::<StyledShape<RectShader>>();
+        assert_eq!(registry.pending_main.len(), 3);
+
+        registry.unregister::<RectShaderExtraction>();
+        assert!(!registry.is_registered::<RectShaderExtraction>());
+        assert!(registry.is_registered::<StyledShape<RectShader>>());
+
+        registry.register::<RectShaderExtraction>();
+        assert!(registry.is_registered::<RectShaderExtraction>());
+        assert_eq!(registry.pending_main.len(), 3);
+    }
+}
@@ -0,0 +1,50 @@
+use bevy::reflect::Struct;
+
+use crate::{
+    parameterized_shader::ParameterizedShader, shader_pipeline::texture_group_index,
+    shader_uniforms::ShaderUniforms, ShapeVertex,
+};
+
+/// Everything the vertex, fragment and cull shaders of a shader are generated from.
+///
+/// This is usually taken from a [`ParameterizedShader`], but shaders defined at runtime can describe themselves directly
+pub(crate) struct ShaderCode {
+    /// Names the generated shaders
+    pub type_path: String,
+    /// A struct with one field for each param, in the order they are laid out in each instance
+    pub params: Box<dyn Struct>,
+    /// The number of words of each instance
+    pub stride: u32,
+    pub fragment_body: String,
+    pub frame_expression: String,
+    /// The import paths of the modules the fragment shader imports
+    pub imports: Vec<String>,
+    pub vertex_functions: String,
+    pub fragment_functions: String,
+    pub use_time: bool,
+    /// A struct with one field for each uniform, if the shader has uniforms
+    pub uniforms: Option<Box<dyn Struct>>,
+    /// The bind group of the texture, if the shader samples one
+    pub texture_group: Option<usize>,
+}
+
+impl ShaderCode {
+    pub fn of<Shader: ParameterizedShader>() -> Self {
+        Self {
+            type_path: Shader::type_path().to_string(),
+            params: Box::new(<Shader::Params as Default>::default()),
+            stride: (std::mem::size_of::<ShapeVertex<Shader::Params>>() / 4) as u32,
+            fragment_body: Shader::fragment_body().into(),
+            frame_expression: Shader::frame_expression().into(),
+            imports: Shader::imports()
+                .map(|import| import.import_path.to_string())
+                .collect(),
+            vertex_functions: Shader::vertex_functions().into(),
+            fragment_functions: Shader::fragment_functions().into(),
+            use_time: Shader::USE_TIME,
+            uniforms: Shader::Uniforms::ENABLED
+                .then(|| Box::new(<Shader::Uniforms as Default>::default()) as Box<dyn Struct>),
+            texture_group: Shader::USE_TEXTURE.then(texture_group_index::<Shader>),
+        }
+    }
+}
@@ -2,7 +2,10 @@ use std::marker::PhantomData;
 
 use bevy::{prelude::*, utils::HashSet};
 
-use crate::{fragment_shader, parameterized_shader::*, vertex_shader};
+use crate::{
+    cull_shader, fragment_body::FragmentBody, fragment_shader, parameterized_shader::*,
+    shader_code::ShaderCode, vertex_shader,
+};
 
 pub const fn get_vertex_asset_id<Shader: ParameterizedShader>(
 ) -> AssetId<bevy::render::render_resource::Shader> {
@@ -20,6 +23,14 @@ pub const fn get_fragment_asset_id<Shader: ParameterizedShader>(
     AssetId::Uuid{ uuid: uuid::Uuid::from_u128(new_id)}
 }
 
+pub const fn get_cull_asset_id<Shader: ParameterizedShader>(
+) -> AssetId<bevy::render::render_resource::Shader> {
+    let id = 73590127461843920511u128;
+    let new_id = id.wrapping_add(Shader::UUID.wrapping_mul(2875688479));
+
+    AssetId::Uuid{ uuid: uuid::Uuid::from_u128(new_id)}
+}
+
 pub const fn get_vertex_handle<Shader: ParameterizedShader>(
 ) -> Handle<bevy::render::render_resource::Shader> {
     Handle::Weak( get_vertex_asset_id::<Shader>())
@@ -30,16 +41,9 @@ pub const fn get_fragment_handle<Shader: ParameterizedShader>(
     Handle::Weak( get_fragment_asset_id::<Shader>())
 }
 
-pub struct ShaderLoadingPlugin<Shader: ParameterizedShader> {
-    phantom: PhantomData<Shader>,
-}
-
-impl<Shader: ParameterizedShader> Default for ShaderLoadingPlugin<Shader> {
-    fn default() -> Self {
-        Self {
-            phantom: Default::default(),
-        }
-    }
+pub const fn get_cull_handle<Shader: ParameterizedShader>(
+) -> Handle<bevy::render::render_resource::Shader> {
+    Handle::Weak( get_cull_asset_id::<Shader>())
 }
 
 #[derive(Debug, Resource, Default)]
@@ -47,35 +51,142 @@ struct LoadedShaderHandles {
     set: HashSet<Handle<bevy::render::render_resource::Shader>>,
 }
 
-impl<Shader: ParameterizedShader> Plugin for ShaderLoadingPlugin<Shader> {
-    fn build(&self, app: &mut App) {
+/// Loads the imports of a shader and adds its generated shaders
+pub(crate) fn load_shaders<Shader: ParameterizedShader>(world: &mut World) {
+    world.init_resource::<LoadedShaderHandles>();
+    let code = ShaderCode::of::<Shader>();
+    let vertex_shader = vertex_shader::create_vertex_shader(&code);
+
+    let asset_server = world.resource_mut::<AssetServer>();
+
+    let mut handles: Vec<Handle<bevy::render::render_resource::Shader>> = vec![];
 
-        app.init_resource::<LoadedShaderHandles>();
-        let vertex_shader = vertex_shader::create_vertex_shader::<Shader>();
+    for import in Shader::imports() {
+        let handle: Handle<bevy::render::render_resource::Shader> = asset_server.load(import.path);
 
+        handles.push(handle);
+    }
+
+    let body = Shader::FRAGMENT_BODY_PATH.map(|path| asset_server.load(path));
 
-        let asset_server = app.world_mut().resource_mut::<AssetServer>();
+    let mut handles_res = world.resource_mut::<LoadedShaderHandles>();
 
-        let mut handles: Vec<Handle<bevy::render::render_resource::Shader>> = vec![];
+    handles_res.set.extend(handles);
 
-        for import in Shader::imports() {
-            let handle: Handle<bevy::render::render_resource::Shader> =
-                asset_server.load(import.path);
+    if let Some(body) = body {
+        world.insert_resource(FragmentBodyHandle::<Shader> {
+            handle: body,
+            phantom: PhantomData,
+        });
+    }
 
-            handles.push(handle);
+    let mut shaders = world.resource_mut::<Assets<bevy::render::render_resource::Shader>>();
+    let fragment_shader = fragment_shader::create_fragment_shader(&code);
+    let cull_shader = cull_shader::create_cull_shader(&code);
+
+    //TODO check for duplicate asset ids here
+    // shaders which were generated before the shader was set up, such as those of dynamic shaders, are kept
+    for (id, shader) in [
+        (get_vertex_asset_id::<Shader>(), vertex_shader),
+        (get_fragment_asset_id::<Shader>(), fragment_shader),
+        (get_cull_asset_id::<Shader>(), cull_shader),
+    ] {
+        if !shaders.contains(id) {
+            shaders.insert(id, shader);
         }
+    }
+}
+
+/// The fragment body of a shader which is loaded from a file
+#[derive(Resource)]
+pub(crate) struct FragmentBodyHandle<Shader: ParameterizedShader> {
+    handle: Handle<FragmentBody>,
+    phantom: PhantomData<Shader>,
+}
 
-        let mut handles_res = app.world_mut().resource_mut::<LoadedShaderHandles>();
+/// Regenerates the fragment shader of a shader when the file containing its body loads or changes
+pub(crate) fn update_fragment_body<Shader: ParameterizedShader>(
+    mut events: EventReader<AssetEvent<FragmentBody>>,
+    body: Res<FragmentBodyHandle<Shader>>,
+    bodies: Res<Assets<FragmentBody>>,
+    mut shaders: ResMut<Assets<bevy::render::render_resource::Shader>>,
+) {
+    let id = body.handle.id();
+
+    // the file may have already been loaded for another shader
+    let mut changed = body.is_added();
+    for event in events.read() {
+        changed |= event.is_loaded_with_dependencies(id) || event.is_modified(id);
+    }
+    if !changed {
+        return;
+    }
 
-        handles_res.set.extend(handles);
+    let Some(body) = bodies.get(id) else {
+        return;
+    };
+
+    let code = ShaderCode {
+        fragment_body: body.0.clone(),
+        ..ShaderCode::of::<Shader>()
+    };
+    shaders.insert(
+        get_fragment_asset_id::<Shader>(),
+        fragment_shader::create_fragment_shader(&code),
+    );
+}
 
-        let mut shaders = app
-            .world_mut()
-            .resource_mut::<Assets<bevy::render::render_resource::Shader>>();
-        let fragment_shader = fragment_shader::create_fragment_shader::<Shader>();
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::System;
+
+    use super::*;
+    use crate::primitives::RectShader;
+
+    /// The source of the generated fragment shader
+    fn fragment_source(world: &World) -> Option<String> {
+        world
+            .resource::<Assets<bevy::render::render_resource::Shader>>()
+            .get(get_fragment_asset_id::<RectShader>())
+            .map(|shader| shader.source.as_str().to_string())
+    }
 
-        //TODO check for duplicate asset ids here
-        shaders.insert(get_vertex_asset_id::<Shader>(), vertex_shader);
-        shaders.insert(get_fragment_asset_id::<Shader>(), fragment_shader);
+    #[test]
+    fn fragment_shader_is_regenerated_when_its_body_changes() {
+        let mut world = World::new();
+        world.init_resource::<Assets<bevy::render::render_resource::Shader>>();
+        world.init_resource::<Assets<FragmentBody>>();
+        world.init_resource::<Events<AssetEvent<FragmentBody>>>();
+        let handle = world
+            .resource_mut::<Assets<FragmentBody>>()
+            .add(FragmentBody("return vec4<f32>(1.0);".to_string()));
+        world.insert_resource(FragmentBodyHandle::<RectShader> {
+            handle: handle.clone(),
+            phantom: PhantomData,
+        });
+
+        let mut system = IntoSystem::into_system(update_fragment_body::<RectShader>);
+        system.initialize(&mut world);
+
+        system.run((), &mut world);
+        assert!(fragment_source(&world)
+            .unwrap()
+            .contains("return vec4<f32>(1.0);"));
+
+        // unchanged bodies are not generated again
+        world
+            .resource_mut::<Assets<bevy::render::render_resource::Shader>>()
+            .remove(get_fragment_asset_id::<RectShader>());
+        system.run((), &mut world);
+        assert_eq!(fragment_source(&world), None);
+
+        world
+            .resource_mut::<Assets<FragmentBody>>()
+            .insert(&handle, FragmentBody("return vec4<f32>(0.5);".to_string()));
+        world.send_event(AssetEvent::Modified { id: handle.id() });
+        system.run((), &mut world);
+        assert!(fragment_source(&world)
+            .unwrap()
+            .contains("return vec4<f32>(0.5);"));
     }
 }
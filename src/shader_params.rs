@@ -1,10 +1,19 @@
 use bevy::{
     color::{LinearRgba, Srgba},
-    reflect::{Reflect, Struct},
+    reflect::{GetTypeRegistration, Reflect, Struct, TypePath},
 };
 use bytemuck::{Pod, Zeroable};
 pub trait ShaderParams:
-    Pod + Zeroable + Copy + std::fmt::Debug + Default + Reflect + Struct + PartialEq
+    Pod
+    + Zeroable
+    + Copy
+    + std::fmt::Debug
+    + Default
+    + Reflect
+    + Struct
+    + TypePath
+    + GetTypeRegistration
+    + PartialEq
 {
 }
 
@@ -46,4 +55,4 @@ impl From<Srgba> for ColorParams {
             color: value.into(),
         }
     }
-}
\ No newline at end of file
+}
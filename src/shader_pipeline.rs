@@ -5,10 +5,12 @@ use bevy::{
     render::{
         globals::GlobalsUniform,
         render_resource::{
-            BindGroupLayout,  BindGroupLayoutEntry, BindingType,
+            binding_types::{sampler, texture_2d},
+            BindGroupLayout, BindGroupLayoutEntries, BindGroupLayoutEntry, BindingType,
             BlendState, BufferBindingType, ColorTargetState, ColorWrites, Face, FragmentState,
             FrontFace, MultisampleState, PolygonMode, PrimitiveState, RenderPipelineDescriptor,
-            ShaderStages, ShaderType, SpecializedRenderPipeline, TextureFormat, VertexAttribute,
+            SamplerBindingType, ShaderStages, ShaderType, SpecializedRenderPipeline, TextureFormat,
+            TextureSampleType, VertexAttribute,
             VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
         },
         renderer::RenderDevice,
@@ -17,13 +19,16 @@ use bevy::{
     },
 };
 
-use crate::parameterized_shader::*;
+use crate::{parameterized_shader::*, shader_uniforms::ShaderUniforms};
 
 use std::marker::PhantomData;
 
 #[derive(Resource)]
 pub(crate) struct ShaderPipeline<Shader: ParameterizedShader> {
     pub view_layout: BindGroupLayout,
+    pub instance_layout: BindGroupLayout,
+    pub uniforms_layout: BindGroupLayout,
+    pub texture_layout: BindGroupLayout,
     phantom: PhantomData<Shader>,
 }
 
@@ -72,13 +77,64 @@ impl<Shader: ParameterizedShader> FromWorld for ShaderPipeline<Shader> {
             render_device.create_bind_group_layout("shape_view_layout", ENTRIES_WITHOUT_TIME)
         };
 
+        const INSTANCE_ENTRIES: &[BindGroupLayoutEntry] = &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+
+        let instance_layout =
+            render_device.create_bind_group_layout("shape_instance_layout", INSTANCE_ENTRIES);
+
+        let uniforms_layout = render_device.create_bind_group_layout(
+            "shape_uniforms_layout",
+            &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(<Shader::Uniforms as ShaderType>::min_size()),
+                },
+                count: None,
+            }],
+        );
+
+        let texture_layout = render_device.create_bind_group_layout(
+            "shape_texture_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                ),
+            ),
+        );
+
         Self {
             view_layout,
+            instance_layout,
+            uniforms_layout,
+            texture_layout,
             phantom: PhantomData,
         }
     }
 }
 
+/// The index of the bind group of the shape's image, which follows the uniforms if there are any
+pub(crate) fn texture_group_index<Shader: ParameterizedShader>() -> usize {
+    if Shader::Uniforms::ENABLED {
+        3
+    } else {
+        2
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub(crate) struct ShaderPipelineKey {
     pub mesh: PipelineKey,
@@ -91,69 +147,26 @@ impl<Shader: ParameterizedShader> SpecializedRenderPipeline for ShaderPipeline<S
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
         // debug!("specializing for {fragment_shader:?}");
 
-        // an f32 is 4 bytes
-        const WORD_BYTE_LENGTH: u64 = 4;
-
-        const ROTATION_WORDS: u64 = 2;
-        const POSITION_WORDS: u64 = 3;
-        const SCALE_WORDS: u64 = 1;
-
-        const CONSTANT_PARAMS: usize = 3;
-
-        let proxy = <Shader::Params as Default>::default();
-        let param_count = proxy.field_len() as u32;
-
-        // (GOTCHA! attributes are sorted alphabetically, and offsets need to reflect this)
+        // Each instance is just the index of its slot in the instance buffer
+        let vertex_attributes = vec![VertexAttribute {
+            format: VertexFormat::Uint32,
+            offset: 0,
+            shader_location: 0,
+        }];
 
-        let pre_param_attributes: [VertexAttribute; CONSTANT_PARAMS] = [
-            // Rotation
-            VertexAttribute {
-                format: VertexFormat::Float32x2,
-                offset: 0,
-                shader_location: 0,
-            },
-            // Position
-            VertexAttribute {
-                format: VertexFormat::Float32x3,
-                offset: ROTATION_WORDS * WORD_BYTE_LENGTH,
-                shader_location: 1,
-            },
-            // Scale
-            VertexAttribute {
-                format: VertexFormat::Float32,
-                offset: (ROTATION_WORDS + POSITION_WORDS) * WORD_BYTE_LENGTH,
-                shader_location: 2,
-            },
+        let mut layout = vec![
+            // Bind group 0 is the view uniform
+            self.view_layout.clone(),
+            // Bind group 1 is the instance data
+            self.instance_layout.clone(),
         ];
-
-        // Customize how to store the meshes' vertex attributes in the vertex buffer
-        // Our meshes only have position, color and params
-        let mut vertex_attributes =
-            Vec::with_capacity(pre_param_attributes.len() + param_count as usize);
-
-        vertex_attributes.extend_from_slice(&pre_param_attributes);
-
-        let mut offset = (POSITION_WORDS + ROTATION_WORDS + SCALE_WORDS) * WORD_BYTE_LENGTH;
-        let mut shader_location: u32 = CONSTANT_PARAMS as u32;
-
-        for field in proxy.iter_fields() {
-            let Some(format) = crate::helpers::get_vertex_format(field.type_id()) else {
-                panic!(
-                    "Cannot convert {} to wgsl type",
-                    field
-                        .get_represented_type_info()
-                        .map(|info| info.type_path())
-                        .unwrap_or_else(|| field.reflect_type_path())
-                );
-            };
-
-            vertex_attributes.push(VertexAttribute {
-                format,
-                offset,
-                shader_location,
-            });
-            offset += format.size();
-            shader_location += 1;
+        if Shader::Uniforms::ENABLED {
+            // Bind group 2 is the shader's uniforms
+            layout.push(self.uniforms_layout.clone());
+        }
+        if Shader::USE_TEXTURE {
+            // The next bind group is the shape's image
+            layout.push(self.texture_layout.clone());
         }
 
         RenderPipelineDescriptor {
@@ -162,7 +175,7 @@ impl<Shader: ParameterizedShader> SpecializedRenderPipeline for ShaderPipeline<S
                 entry_point: "vertex".into(),
                 shader_defs: Vec::new(),
                 buffers: vec![VertexBufferLayout {
-                    array_stride: offset,
+                    array_stride: VertexFormat::Uint32.size(),
                     step_mode: VertexStepMode::Instance,
                     attributes: vertex_attributes,
                 }],
@@ -181,10 +194,7 @@ impl<Shader: ParameterizedShader> SpecializedRenderPipeline for ShaderPipeline<S
                     write_mask: ColorWrites::ALL,
                 })],
             }),
-            layout: vec![
-                // Bind group 0 is the view uniform
-                self.view_layout.clone(),
-            ],
+            layout,
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
                 cull_mode: Some(Face::Back),
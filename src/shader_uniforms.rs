@@ -0,0 +1,140 @@
+use bevy::{
+    ecs::system::{ReadOnlySystemParam, SystemParam},
+    reflect::{Reflect, Struct},
+    render::render_resource::{encase::internal::WriteInto, ShaderType},
+};
+
+/// Values which are the same for every shape drawn with a shader.
+///
+/// These are set once per frame from `ResourceParams` and can be read in the fragment body as `uniforms.<field>`.
+pub trait ShaderUniforms:
+    ShaderType + WriteInto + Clone + Default + Reflect + Struct + Send + Sync + 'static
+{
+    type ResourceParams<'w>: SystemParam + ReadOnlySystemParam;
+
+    /// Whether the shader has uniforms. If false, the uniforms are never bound.
+    const ENABLED: bool = true;
+
+    fn get_uniforms(resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>) -> Self;
+}
+
+pub use no_uniforms::NoUniforms;
+
+mod no_uniforms {
+    // the `ShaderType` derive generates a function for each field which newer compilers report as unused
+    #![allow(dead_code)]
+
+    use bevy::{reflect::Reflect, render::render_resource::ShaderType};
+
+    /// Uniforms for a shader which does not use any
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, ShaderType)]
+    pub struct NoUniforms {
+        /// Uniform structs cannot be empty
+        #[reflect(ignore)]
+        _unused: u32,
+    }
+}
+
+impl ShaderUniforms for NoUniforms {
+    type ResourceParams<'w> = ();
+
+    const ENABLED: bool = false;
+
+    fn get_uniforms(_resource: &()) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*, render::MainWorld};
+
+    use super::*;
+    use crate::{
+        extract_uniforms, fragment_shader,
+        frame::Frame,
+        parameterized_shader::{FragmentImport, ParameterizedShader},
+        primitives::RectShader,
+        shader_code::ShaderCode,
+        shader_params::ColorParams,
+        ExtractedShapes,
+    };
+
+    use tint_uniforms::TintUniforms;
+
+    mod tint_uniforms {
+        // the `ShaderType` derive generates a function for each field which newer compilers report as unused
+        #![allow(dead_code)]
+
+        use bevy::{prelude::*, render::render_resource::ShaderType};
+
+        /// A color which tints every shape
+        #[derive(Debug, Clone, Copy, PartialEq, Default, Reflect, ShaderType)]
+        pub struct TintUniforms {
+            pub tint: LinearRgba,
+        }
+    }
+
+    #[derive(Resource)]
+    pub struct Tint(LinearRgba);
+
+    impl ShaderUniforms for TintUniforms {
+        type ResourceParams<'w> = Res<'w, Tint>;
+
+        fn get_uniforms(resource: &Res<Tint>) -> Self {
+            Self { tint: resource.0 }
+        }
+    }
+
+    #[derive(TypePath)]
+    struct TintedShader;
+
+    impl ParameterizedShader for TintedShader {
+        type Params = ColorParams;
+        type Uniforms = TintUniforms;
+
+        fn fragment_body() -> impl Into<String> {
+            "return in.color * uniforms.tint;"
+        }
+
+        fn frame_expression() -> impl Into<String> {
+            "vec2<f32>(1.0)"
+        }
+
+        fn frame(_params: &ColorParams) -> Frame {
+            Frame::square(1.0)
+        }
+
+        fn imports() -> impl Iterator<Item = FragmentImport> {
+            [].into_iter()
+        }
+
+        const UUID: u128 = 0x6a2c9e4f8b1d4e7391c5a3f0d2b8e6a4;
+    }
+
+    #[test]
+    fn only_shaders_with_uniforms_declare_them() {
+        let tinted = fragment_shader::create_fragment_shader(&ShaderCode::of::<TintedShader>());
+        assert!(tinted
+            .source
+            .as_str()
+            .contains("var<uniform> uniforms: Uniforms;"));
+
+        let rect = fragment_shader::create_fragment_shader(&ShaderCode::of::<RectShader>());
+        assert!(!rect.source.as_str().contains("uniforms"));
+    }
+
+    #[test]
+    fn uniforms_are_extracted_from_their_resources() {
+        let mut main_world = MainWorld::default();
+        main_world.insert_resource(Tint(LinearRgba::GREEN));
+
+        let mut render_world = World::new();
+        render_world.insert_resource(main_world);
+        render_world.init_resource::<ExtractedShapes<TintedShader>>();
+        render_world.run_system_once(extract_uniforms::<TintedShader>);
+
+        let extracted = render_world.resource::<ExtractedShapes<TintedShader>>();
+        assert_eq!(extracted.uniforms.get().tint, LinearRgba::GREEN);
+    }
+}
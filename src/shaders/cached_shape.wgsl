@@ -0,0 +1,52 @@
+struct View {
+    view_proj: mat4x4<f32>,
+    world_position: vec3<f32>,
+};
+@group(0) @binding(0)
+var<uniform> view: View;
+
+@group(1) @binding(0)
+var cache_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var cache_sampler: sampler;
+
+struct Vertex {
+    @location(0) rotation: vec2<f32>,
+    @location(1) position: vec3<f32>,
+    @location(2) scale: f32,
+    @location(3) half_size: vec2<f32>,
+    @location(4) uv_min: vec2<f32>,
+    @location(5) uv_max: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(
+    vertex: Vertex,
+    @builtin(vertex_index) i: u32
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let x = select(-1., 1., i % 2u == 0u);
+    let y = select(-1., 1., (i / 2u) % 2u == 0u);
+    let c = vertex.rotation.x;
+    let s = vertex.rotation.y;
+
+    // the cell was captured by a camera rotated with the shape, so the size is applied before rotating
+    let corner = vec2<f32>(x, y) * vertex.half_size;
+    let rotated = vec2<f32>(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
+    let pos = vertex.position + vec3<f32>(rotated * vertex.scale, 0.);
+    out.clip_position = view.view_proj * vec4<f32>(pos, 1.);
+    // textures are stored top to bottom
+    out.uv = mix(vertex.uv_min, vertex.uv_max, vec2<f32>(x + 1., 1. - y) * 0.5);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(cache_texture, cache_sampler, in.uv);
+}
@@ -0,0 +1,432 @@
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        system::SystemParam,
+    },
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_phase::{sort_phase_system, ViewSortedRenderPhases},
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+        Extract, Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::{ViewShapeBatches, ViewsAwaitingPipelines};
+
+/// Adds support for rendering shapes once into an image using [`ShapeSnapshots`]
+pub struct ShapeSnapshotPlugin;
+
+impl Plugin for ShapeSnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+
+        app.add_event::<ShapeSnapshotReady>()
+            .insert_resource(SnapshotReceiver(Mutex::new(receiver)))
+            .add_systems(First, finish_snapshots);
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(SnapshotSender(sender))
+                .add_systems(ExtractSchedule, extract_snapshot_cameras)
+                .add_systems(
+                    Render,
+                    (
+                        remove_unwanted_phase_items
+                            .in_set(RenderSet::PhaseSort)
+                            .before(sort_phase_system::<Transparent2d>),
+                        report_finished_snapshots
+                            .in_set(RenderSet::Cleanup)
+                            .before(crate::clear_views_awaiting_pipelines)
+                            .before(crate::clear_view_shape_batches),
+                    ),
+                );
+        };
+    }
+}
+
+/// The shapes that a snapshot should include
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotTarget {
+    /// Only these entities will be drawn
+    Entities(Vec<Entity>),
+    /// Every shape on these layers will be drawn
+    Layers(RenderLayers),
+}
+
+/// A request to render some shapes into a new image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeSnapshotRequest {
+    /// The shapes to draw
+    pub target: SnapshotTarget,
+    /// The size of the image in pixels
+    pub size: UVec2,
+    /// The world space area to draw into the image
+    pub area: Rect,
+    /// The color the image is cleared to before drawing
+    pub background: Color,
+}
+
+impl ShapeSnapshotRequest {
+    /// A request to draw the given entities into an image of the given size
+    /// The area defaults to `size` world units centered on the origin with a transparent background
+    pub fn entities(entities: impl IntoIterator<Item = Entity>, size: UVec2) -> Self {
+        Self {
+            target: SnapshotTarget::Entities(entities.into_iter().collect()),
+            size,
+            area: Rect::from_center_size(Vec2::ZERO, size.as_vec2()),
+            background: Color::NONE,
+        }
+    }
+
+    /// A request to draw every shape on the given layers into an image of the given size
+    /// The area defaults to `size` world units centered on the origin with a transparent background
+    pub fn layers(layers: RenderLayers, size: UVec2) -> Self {
+        Self {
+            target: SnapshotTarget::Layers(layers),
+            size,
+            area: Rect::from_center_size(Vec2::ZERO, size.as_vec2()),
+            background: Color::NONE,
+        }
+    }
+
+    pub fn with_area(mut self, area: Rect) -> Self {
+        self.area = area;
+        self
+    }
+
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+/// Sent once a snapshot has been rendered into its image
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct ShapeSnapshotReady {
+    /// The image that the snapshot was rendered into
+    pub image: Handle<Image>,
+    /// Whether every shape was drawn
+    pub status: SnapshotStatus,
+}
+
+/// Which shapes were drawn into a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// Every shape was drawn
+    Complete,
+    /// The pipeline of one of the shaders failed to compile, so its shapes are missing from the image
+    Incomplete,
+    /// No shapes were drawn, such as when none of the requested entities are visible shapes, so the image only has the background
+    NothingToDraw,
+}
+
+/// Marks the camera that renders a snapshot. The camera is despawned once the snapshot is ready.
+#[derive(Debug, Clone, Component)]
+pub struct ShapeSnapshotCamera {
+    image: Handle<Image>,
+    entities: Option<EntityHashSet>,
+}
+
+/// Renders shapes into images
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_param_shaders::snapshot::*;
+///
+/// fn take_snapshot(mut snapshots: ShapeSnapshots, shapes: Query<Entity, With<Selected>>) {
+///     let image = snapshots.take(ShapeSnapshotRequest::entities(shapes.iter(), UVec2::splat(128)));
+///     // `image` can be used straight away, it will be filled in once a `ShapeSnapshotReady` event is sent
+/// }
+/// # #[derive(Component)]
+/// # struct Selected;
+/// ```
+#[derive(SystemParam)]
+pub struct ShapeSnapshots<'w, 's> {
+    commands: Commands<'w, 's>,
+    images: ResMut<'w, Assets<Image>>,
+    layers: Query<'w, 's, Option<&'static RenderLayers>>,
+}
+
+impl<'w, 's> ShapeSnapshots<'w, 's> {
+    /// Start rendering a snapshot. The returned image will be empty until [`ShapeSnapshotReady`] is sent for it.
+    pub fn take(&mut self, request: ShapeSnapshotRequest) -> Handle<Image> {
+        let ShapeSnapshotRequest {
+            target,
+            size,
+            area,
+            background,
+        } = request;
+
+        let image = self.images.add(render_target_image(size));
+
+        let (layers, entities) = match target {
+            SnapshotTarget::Entities(entities) => {
+                // the camera must be able to see every entity for it to be drawn
+                let layers = entities
+                    .iter()
+                    .map(|entity| {
+                        self.layers
+                            .get(*entity)
+                            .ok()
+                            .flatten()
+                            .cloned()
+                            .unwrap_or_default()
+                    })
+                    .fold(RenderLayers::none(), |acc, layers| acc.union(&layers));
+
+                (layers, Some(entities.into_iter().collect()))
+            }
+            SnapshotTarget::Layers(layers) => (layers, None),
+        };
+
+        let center = area.center();
+        let camera = snapshot_camera(
+            image.clone(),
+            area.size(),
+            Transform::from_xyz(center.x, center.y, 0.0),
+            background,
+        );
+
+        self.commands.spawn((
+            camera,
+            layers,
+            ShapeSnapshotCamera {
+                image: image.clone(),
+                entities,
+            },
+        ));
+
+        image
+    }
+}
+
+impl ShapeSnapshotCamera {
+    pub(crate) fn new(image: Handle<Image>, entities: Option<EntityHashSet>) -> Self {
+        Self { image, entities }
+    }
+
+    /// The only entities this camera should draw, if it is restricted
+    pub(crate) fn entities(&self) -> Option<&EntityHashSet> {
+        self.entities.as_ref()
+    }
+}
+
+/// An empty image that cameras can render into
+pub(crate) fn render_target_image(size: UVec2) -> Image {
+    let size = Extent3d {
+        width: size.x.max(1),
+        height: size.y.max(1),
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("shape_snapshot"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+/// A camera which draws `area_size` world units around `transform` into the image
+pub(crate) fn snapshot_camera(
+    image: Handle<Image>,
+    area_size: Vec2,
+    transform: Transform,
+    background: Color,
+) -> Camera2dBundle {
+    let mut camera = Camera2dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(image),
+            clear_color: ClearColorConfig::Custom(background),
+            ..default()
+        },
+        transform,
+        global_transform: transform.into(),
+        ..default()
+    };
+    camera.projection.scaling_mode = ScalingMode::Fixed {
+        width: area_size.x,
+        height: area_size.y,
+    };
+    camera
+}
+
+/// Receives the snapshot views which have finished, and whether all their shapes were drawn
+#[derive(Resource)]
+struct SnapshotReceiver(Mutex<Receiver<(Entity, SnapshotStatus)>>);
+
+#[derive(Resource)]
+struct SnapshotSender(Sender<(Entity, SnapshotStatus)>);
+
+fn extract_snapshot_cameras(
+    mut commands: Commands,
+    cameras: Extract<Query<(Entity, &ShapeSnapshotCamera)>>,
+) {
+    for (entity, camera) in cameras.iter() {
+        commands.get_or_spawn(entity).insert(SnapshotView {
+            only_shapes: camera.entities.is_some(),
+        });
+    }
+}
+
+#[derive(Component)]
+struct SnapshotView {
+    /// Whether the view only draws particular shapes, so anything else on its layers should be left out
+    only_shapes: bool,
+}
+
+/// Removes the phase items which are not shapes, such as sprites and meshes, from views which only draw particular shapes
+fn remove_unwanted_phase_items(
+    views: Query<(Entity, &SnapshotView)>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    view_batches: Res<ViewShapeBatches>,
+) {
+    for (view, snapshot) in views.iter() {
+        if !snapshot.only_shapes {
+            continue;
+        }
+        if let Some(transparent_phase) = transparent_render_phases.get_mut(&view) {
+            transparent_phase
+                .items
+                .retain(|item| view_batches.get_for_item(view, item).is_some());
+        }
+    }
+}
+
+fn report_finished_snapshots(
+    views: Query<Entity, With<SnapshotView>>,
+    views_awaiting_pipelines: Res<ViewsAwaitingPipelines>,
+    view_batches: Res<ViewShapeBatches>,
+    sender: Res<SnapshotSender>,
+) {
+    for view in views.iter() {
+        if views_awaiting_pipelines.awaiting.contains(&view) {
+            continue;
+        }
+        let status = if views_awaiting_pipelines.failed.contains(&view) {
+            SnapshotStatus::Incomplete
+        } else if !view_batches.has_batches(view) {
+            SnapshotStatus::NothingToDraw
+        } else {
+            SnapshotStatus::Complete
+        };
+        let _ = sender.0.send((view, status));
+    }
+}
+
+fn finish_snapshots(
+    mut commands: Commands,
+    receiver: Res<SnapshotReceiver>,
+    cameras: Query<&ShapeSnapshotCamera>,
+    mut events: EventWriter<ShapeSnapshotReady>,
+) {
+    let Ok(receiver) = receiver.0.lock() else {
+        return;
+    };
+
+    // the same snapshot may be reported more than once before the camera is despawned
+    let finished: EntityHashMap<SnapshotStatus> = receiver.try_iter().collect();
+
+    for (entity, status) in finished {
+        let Ok(snapshot) = cameras.get(entity) else {
+            continue;
+        };
+
+        match status {
+            SnapshotStatus::Complete => {}
+            SnapshotStatus::Incomplete => {
+                error!("A shape snapshot was rendered without the shapes of shaders whose pipelines failed to compile");
+            }
+            SnapshotStatus::NothingToDraw => {
+                warn!("A shape snapshot had no shapes to draw");
+            }
+        }
+
+        events.send(ShapeSnapshotReady {
+            image: snapshot.image.clone(),
+            status,
+        });
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::testing::{drawn_items, shape_batch, TestPhases};
+
+    #[test]
+    fn views_of_particular_shapes_only_draw_shapes() {
+        let mut world = World::new();
+        let shapes_view = world.spawn(SnapshotView { only_shapes: true }).id();
+        let layers_view = world.spawn(SnapshotView { only_shapes: false }).id();
+
+        let mut phases = TestPhases::default();
+        for view in [shapes_view, layers_view] {
+            phases.add_other(view);
+            phases.add_shapes(view, shape_batch(0..1, Rect::default()));
+            phases.add_other(view);
+        }
+        phases.insert_into(&mut world);
+        world.run_system_once(remove_unwanted_phase_items);
+
+        assert_eq!(drawn_items(&world, shapes_view), [1]);
+        assert_eq!(drawn_items(&world, layers_view), [0, 1, 2]);
+    }
+
+    #[test]
+    fn snapshots_report_which_shapes_were_drawn() {
+        let mut world = World::new();
+        let view = |world: &mut World| world.spawn(SnapshotView { only_shapes: true }).id();
+        let (complete, incomplete, empty, waiting) = (
+            view(&mut world),
+            view(&mut world),
+            view(&mut world),
+            view(&mut world),
+        );
+
+        let mut phases = TestPhases::default();
+        for view in [complete, incomplete, waiting] {
+            phases.add_shapes(view, shape_batch(0..1, Rect::default()));
+        }
+        phases.insert_into(&mut world);
+
+        let mut views_awaiting_pipelines = ViewsAwaitingPipelines::default();
+        views_awaiting_pipelines.failed.insert(incomplete);
+        views_awaiting_pipelines.awaiting.insert(waiting);
+        world.insert_resource(views_awaiting_pipelines);
+
+        let (sender, receiver) = channel();
+        world.insert_resource(SnapshotSender(sender));
+        world.run_system_once(report_finished_snapshots);
+
+        let finished: EntityHashMap<SnapshotStatus> = receiver.try_iter().collect();
+        assert_eq!(finished.len(), 3);
+        assert_eq!(finished[&complete], SnapshotStatus::Complete);
+        assert_eq!(finished[&incomplete], SnapshotStatus::Incomplete);
+        assert_eq!(finished[&empty], SnapshotStatus::NothingToDraw);
+    }
+}
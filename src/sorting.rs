@@ -0,0 +1,423 @@
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    prelude::*,
+    render::{
+        render_phase::{DrawFunctionId, ViewSortedRenderPhases},
+        render_resource::CachedRenderPipelineId,
+    },
+    utils::{FixedState, HashMap},
+};
+use std::hash::BuildHasher;
+
+use crate::{
+    components::{ParamShaderSortMode, ShaderYSortOffset, ShaderZIndex, ShapeBatchReordering},
+    ViewShapeBatches,
+};
+
+/// Where a shape is drawn relative to other shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ShapeSortKey {
+    /// Used as the sort key of the phase item
+    pub depth: f32,
+    /// Orders shapes with the same depth
+    pub order: f32,
+}
+
+/// The values a shape can be sorted by, so that each view can sort it using its own [`ParamShaderSortMode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ShapeSortInputs {
+    z: f32,
+    y: f32,
+    z_index: Option<ShaderZIndex>,
+}
+
+impl ShapeSortInputs {
+    pub fn new(
+        transform: &GlobalTransform,
+        z_index: Option<&ShaderZIndex>,
+        y_offset: Option<&ShaderYSortOffset>,
+    ) -> Self {
+        let translation = transform.translation();
+        Self {
+            z: translation.z,
+            y: translation.y + y_offset.map_or(0.0, |offset| offset.0),
+            z_index: z_index.copied(),
+        }
+    }
+
+    pub fn key(&self, mode: ParamShaderSortMode) -> ShapeSortKey {
+        // shapes further down the screen are drawn in front
+        match mode {
+            ParamShaderSortMode::Z => match self.z_index {
+                Some(z_index) => ShapeSortKey {
+                    depth: z_index.layer as f32,
+                    order: z_index.order,
+                },
+                None => ShapeSortKey {
+                    depth: self.z,
+                    order: 0.0,
+                },
+            },
+            ParamShaderSortMode::Y => ShapeSortKey {
+                depth: -self.y,
+                order: self.z_index.map_or(0.0, |z_index| z_index.order),
+            },
+            ParamShaderSortMode::ZThenY => ShapeSortKey {
+                depth: self.z_index.map_or(self.z, |z_index| z_index.layer as f32),
+                order: -self.y,
+            },
+        }
+    }
+}
+
+/// The order that views with each sort mode draw the instances of a frame in.
+///
+/// The instances are written to the GPU once for each mode in use, one mode after another.
+#[derive(Debug, Default)]
+pub(crate) struct SortedInstances {
+    /// Each mode, the position of its first instance in the buffer, and the indices of the instances in sorted order
+    modes: Vec<(ParamShaderSortMode, u32, Vec<u32>)>,
+}
+
+impl SortedInstances {
+    /// Sorts the instances for each of the modes, calling `push` with each instance in the order it should be written to the buffer
+    pub fn sort<T>(
+        &mut self,
+        modes: impl IntoIterator<Item = ParamShaderSortMode>,
+        instances: &[T],
+        key: impl Fn(&T, ParamShaderSortMode) -> (ShapeSortKey, u64, Entity),
+        mut push: impl FnMut(&T),
+    ) {
+        self.modes.clear();
+        let mut position = 0;
+
+        for mode in modes {
+            if self.modes.iter().any(|(m, ..)| *m == mode) {
+                continue;
+            }
+            let mut order: Vec<u32> = (0..instances.len() as u32).collect();
+            sort_by_shape_key(&mut order, |index| key(&instances[*index as usize], mode));
+            for index in order.iter() {
+                push(&instances[*index as usize]);
+            }
+
+            self.modes.push((mode, position, order));
+            position += instances.len() as u32;
+        }
+    }
+
+    /// The instances in the order they are drawn with this mode, along with their position in the buffer
+    pub fn iter<'a, T>(
+        &'a self,
+        mode: ParamShaderSortMode,
+        instances: &'a [T],
+    ) -> impl Iterator<Item = (u32, &'a T)> + 'a {
+        self.modes
+            .iter()
+            .filter(move |(m, ..)| *m == mode)
+            .flat_map(move |(_, start, order)| {
+                order
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, index)| (start + i as u32, &instances[*index as usize]))
+            })
+    }
+
+    pub fn clear(&mut self) {
+        self.modes.clear();
+    }
+}
+
+/// Sorts by depth, then order, then group, then entity so that shapes with equal keys are drawn in the same order each frame.
+///
+/// Shapes with equal keys and the same group, such as the image they use, can then be drawn together.
+pub(crate) fn sort_by_shape_key<T>(
+    items: &mut [T],
+    key: impl Fn(&T) -> (ShapeSortKey, u64, Entity),
+) {
+    // radsort is stable so sort by the least significant key first
+    radsort::sort_by_key(items, |item| key(item).2.to_bits());
+    radsort::sort_by_key(items, |item| key(item).1);
+    radsort::sort_by_key(items, |item| key(item).0.order);
+    radsort::sort_by_key(items, |item| key(item).0.depth);
+}
+
+/// The group of shapes which use this image, which is the same each frame
+pub(crate) fn image_sort_group(image: Option<AssetId<Image>>) -> u64 {
+    image.map_or(0, |image| FixedState.hash_one(image))
+}
+
+/// Phase items with the same sort key are ordered by whichever system queued them first.
+/// This puts shape phase items with the same sort key in order of their batch's order and then their entity.
+pub(crate) fn sort_shape_ties(
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    view_batches: Res<ViewShapeBatches>,
+) {
+    let mut positions = vec![];
+    let mut items = vec![];
+
+    for (view, transparent_phase) in transparent_render_phases.0.iter_mut() {
+        let mut start = 0;
+        while start < transparent_phase.items.len() {
+            let sort_key = transparent_phase.items[start].sort_key;
+            let end = transparent_phase.items[start..]
+                .iter()
+                .position(|item| item.sort_key != sort_key)
+                .map_or(transparent_phase.items.len(), |len| start + len);
+
+            positions.clear();
+            items.clear();
+            for (index, item) in transparent_phase.items[start..end].iter().enumerate() {
+                if let Some(batch) = view_batches.get_for_item(*view, item) {
+                    positions.push(start + index);
+                    items.push((batch.order, item.entity, copy_item(item)));
+                }
+            }
+
+            if items.len() > 1 {
+                radsort::sort_by_key(&mut items, |(_, entity, _)| entity.to_bits());
+                radsort::sort_by_key(&mut items, |(order, _, _)| *order);
+                for (position, (_, _, item)) in positions.iter().zip(items.drain(..)) {
+                    transparent_phase.items[*position] = item;
+                }
+            }
+
+            start = end;
+        }
+    }
+}
+
+/// Phase items which will be drawn one after another
+pub(crate) struct ItemGroup {
+    items: Vec<Transparent2d>,
+    /// The bounds of the items, or `None` if they are not known
+    bounds: Option<Rect>,
+}
+
+/// Identifies the group a shape batch can join: the group must end with the instance before the batch's first instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct GroupKey {
+    draw_function: DrawFunctionId,
+    pipeline: CachedRenderPipelineId,
+    image: Option<AssetId<Image>>,
+    end: u32,
+}
+
+/// How many groups an item can be moved back past, which limits the cost of checking for overlaps
+const MAX_REORDER_DISTANCE: usize = 64;
+
+/// Moves shape phase items back to follow the phase item which draws the instances just before theirs,
+/// as long as they do not overlap anything drawn in between.
+/// `join_adjacent_batches` can then draw them together.
+pub(crate) fn reorder_non_overlapping_batches(
+    reordering: Res<ShapeBatchReordering>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    view_batches: Res<ViewShapeBatches>,
+    mut groups: Local<Vec<ItemGroup>>,
+    mut group_ends: Local<HashMap<GroupKey, usize>>,
+) {
+    if *reordering == ShapeBatchReordering::Disabled {
+        return;
+    }
+
+    for (view, transparent_phase) in transparent_render_phases.0.iter_mut() {
+        groups.clear();
+        group_ends.clear();
+
+        for item in transparent_phase.items.drain(..) {
+            let Some(batch) = view_batches.get_for_item(*view, &item) else {
+                groups.push(ItemGroup {
+                    items: vec![item],
+                    bounds: None,
+                });
+                continue;
+            };
+
+            let key = GroupKey {
+                draw_function: item.draw_function,
+                pipeline: item.pipeline,
+                image: batch.image,
+                end: batch.range.start,
+            };
+            let next_key = GroupKey {
+                end: batch.range.end,
+                ..key
+            };
+
+            let target = group_ends.remove(&key).filter(|index| {
+                groups.len() - index <= MAX_REORDER_DISTANCE
+                    && groups[index + 1..].iter().all(|group| {
+                        group
+                            .bounds
+                            .is_some_and(|bounds| !overlaps(bounds, batch.bounds))
+                    })
+            });
+
+            match target {
+                Some(index) => {
+                    let group = &mut groups[index];
+                    group.items.push(item);
+                    group.bounds = group.bounds.map(|bounds| bounds.union(batch.bounds));
+                    group_ends.insert(next_key, index);
+                }
+                None => {
+                    groups.push(ItemGroup {
+                        items: vec![item],
+                        bounds: Some(batch.bounds),
+                    });
+                    group_ends.insert(next_key, groups.len() - 1);
+                }
+            }
+        }
+
+        transparent_phase
+            .items
+            .extend(groups.drain(..).flat_map(|group| group.items));
+    }
+}
+
+/// Whether the rectangles overlap, counting touching edges as overlapping
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+}
+
+fn copy_item(item: &Transparent2d) -> Transparent2d {
+    Transparent2d {
+        sort_key: item.sort_key,
+        entity: item.entity,
+        pipeline: item.pipeline,
+        draw_function: item.draw_function,
+        batch_range: item.batch_range.clone(),
+        extra_index: item.extra_index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::testing::{drawn_items, shape_batch, TestPhases};
+
+    fn key(depth: f32, order: f32) -> ShapeSortKey {
+        ShapeSortKey { depth, order }
+    }
+
+    #[test]
+    fn shapes_are_sorted_by_depth_then_order_then_group_then_entity() {
+        let entity = Entity::from_raw;
+        let mut items = vec![
+            (key(1.0, 0.0), 0, entity(0)),
+            (key(0.0, 1.0), 0, entity(1)),
+            (key(0.0, 0.0), 1, entity(2)),
+            (key(0.0, 0.0), 0, entity(4)),
+            (key(0.0, 0.0), 0, entity(3)),
+            (key(-1.0, 5.0), 2, entity(5)),
+        ];
+        sort_by_shape_key(&mut items, |item| *item);
+
+        let entities: Vec<u32> = items.iter().map(|(.., entity)| entity.index()).collect();
+        assert_eq!(entities, [5, 3, 4, 2, 1, 0]);
+    }
+
+    #[test]
+    fn z_index_replaces_z() {
+        let transform = GlobalTransform::from_xyz(0.0, 0.0, 100.0);
+        let z_index = ShaderZIndex::new(-2, 0.5);
+
+        let inputs = ShapeSortInputs::new(&transform, Some(&z_index), None);
+        assert_eq!(inputs.key(ParamShaderSortMode::Z), key(-2.0, 0.5));
+
+        let inputs = ShapeSortInputs::new(&transform, None, None);
+        assert_eq!(inputs.key(ParamShaderSortMode::Z), key(100.0, 0.0));
+    }
+
+    #[test]
+    fn instances_are_written_once_for_each_mode() {
+        // (y, entity) of shapes with the same z
+        let instances = [(1.0, 0), (-1.0, 1), (0.0, 2)];
+        let key = |(y, entity): &(f32, u32), mode| {
+            let transform = GlobalTransform::from_xyz(0.0, *y, 0.0);
+            let inputs = ShapeSortInputs::new(&transform, None, None);
+            (inputs.key(mode), 0, Entity::from_raw(*entity))
+        };
+
+        let mut sorted = SortedInstances::default();
+        let mut written = vec![];
+        sorted.sort(
+            [
+                ParamShaderSortMode::Z,
+                ParamShaderSortMode::Y,
+                ParamShaderSortMode::Z,
+            ],
+            &instances,
+            key,
+            |(_, entity)| written.push(*entity),
+        );
+        assert_eq!(written, [0, 1, 2, 0, 2, 1]);
+
+        let drawn = |mode| -> Vec<(u32, u32)> {
+            sorted
+                .iter(mode, &instances)
+                .map(|(position, (_, entity))| (position, *entity))
+                .collect()
+        };
+        assert_eq!(drawn(ParamShaderSortMode::Z), [(0, 0), (1, 1), (2, 2)]);
+        // shapes further down the screen are drawn last
+        assert_eq!(drawn(ParamShaderSortMode::Y), [(3, 0), (4, 2), (5, 1)]);
+        assert_eq!(drawn(ParamShaderSortMode::ZThenY), []);
+    }
+
+    /// Reorders phase items for shape batches with the given first instance and bounds, returning the order they are drawn in.
+    /// `None` is a phase item which is not drawn by a shape, such as a sprite
+    fn reorder(items: &[Option<(u32, Rect)>]) -> Vec<u32> {
+        let view = Entity::from_raw(0);
+        let mut phases = TestPhases::default();
+        for item in items {
+            match item {
+                Some((start, bounds)) => {
+                    phases.add_shapes(view, shape_batch(*start..start + 1, *bounds));
+                }
+                None => phases.add_other(view),
+            }
+        }
+
+        let mut world = World::new();
+        world.insert_resource(ShapeBatchReordering::NonOverlapping);
+        phases.insert_into(&mut world);
+        world.run_system_once(reorder_non_overlapping_batches);
+
+        drawn_items(&world, view)
+    }
+
+    fn square(x: f32) -> Rect {
+        Rect::from_center_size(Vec2::new(x, 0.0), Vec2::ONE)
+    }
+
+    #[test]
+    fn batches_are_moved_past_shapes_they_do_not_overlap() {
+        let order = reorder(&[
+            Some((0, square(0.0))),
+            Some((5, square(10.0))),
+            Some((1, square(20.0))),
+            Some((2, square(30.0))),
+        ]);
+        assert_eq!(order, [0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn batches_are_not_moved_past_shapes_they_overlap() {
+        let order = reorder(&[
+            Some((0, square(0.0))),
+            Some((5, square(10.0))),
+            Some((1, square(10.5))),
+        ]);
+        assert_eq!(order, [0, 1, 2]);
+    }
+
+    #[test]
+    fn batches_are_not_moved_past_other_items() {
+        let order = reorder(&[Some((0, square(0.0))), None, Some((1, square(20.0)))]);
+        assert_eq!(order, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{any::TypeId, marker::PhantomData};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::{query::WorldQuery, system::SystemParam},
+    prelude::*,
+    reflect::{serde::TypedReflectDeserializer, TypeRegistryArc},
+};
+use serde::de::DeserializeSeed;
+
+use crate::{
+    parameterized_shader::{ExtractToShader, ParameterizedShader},
+    ExtractToShaderPlugin,
+};
+
+/// Params shared by many shapes, like a material.
+///
+/// Shapes drawn with [`StyledShape`] take their params from their `Handle<ShapeStyle<Shader>>`,
+/// so changing the style changes every shape which uses it.
+/// Styles can be loaded from `.style.ron` files containing the fields of the params, which are hot reloaded when the `file_watcher` feature is enabled.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct ShapeStyle<Shader: ParameterizedShader> {
+    pub params: Shader::Params,
+}
+
+impl<Shader: ParameterizedShader> ShapeStyle<Shader> {
+    pub fn new(params: Shader::Params) -> Self {
+        Self { params }
+    }
+}
+
+/// Draws shapes with the params of their `Handle<ShapeStyle<Shader>>`.
+///
+/// Shapes whose style has not loaded yet are drawn with the default params.
+#[derive(TypePath)]
+pub struct StyledShape<Shader: ParameterizedShader>(PhantomData<Shader>);
+
+impl<Shader: ParameterizedShader> ExtractToShader for StyledShape<Shader> {
+    type Shader = Shader;
+    type ParamsQuery<'a> = &'a Handle<ShapeStyle<Shader>>;
+    type ParamsBundle = Handle<ShapeStyle<Shader>>;
+    type ResourceParams<'w> = Res<'w, Assets<ShapeStyle<Shader>>>;
+    type Filter = ();
+
+    fn get_params(
+        query_item: <Self::ParamsQuery<'_> as WorldQuery>::Item<'_>,
+        resource: &<Self::ResourceParams<'_> as SystemParam>::Item<'_, '_>,
+    ) -> Shader::Params {
+        resource
+            .get(query_item)
+            .map(|style| style.params)
+            .unwrap_or_default()
+    }
+}
+
+/// Adds the [`ShapeStyle`] asset for a shader and draws shapes with [`StyledShape`]
+pub struct ShapeStylePlugin<Shader: ParameterizedShader>(PhantomData<Shader>);
+
+impl<Shader: ParameterizedShader> Default for ShapeStylePlugin<Shader> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<Shader: ParameterizedShader> Plugin for ShapeStylePlugin<Shader> {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Shader::Params>()
+            .init_asset::<ShapeStyle<Shader>>()
+            .init_asset_loader::<ShapeStyleLoader<Shader>>()
+            .add_plugins(ExtractToShaderPlugin::<StyledShape<Shader>>::default());
+    }
+}
+
+/// Loads a [`ShapeStyle`] from a RON file containing the fields of the params
+struct ShapeStyleLoader<Shader: ParameterizedShader> {
+    type_registry: TypeRegistryArc,
+    phantom: PhantomData<Shader>,
+}
+
+impl<Shader: ParameterizedShader> FromWorld for ShapeStyleLoader<Shader> {
+    fn from_world(world: &mut World) -> Self {
+        // the registry is shared, so types registered after the loader is added can still be read
+        Self {
+            type_registry: world.resource::<AppTypeRegistry>().0.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShapeStyleLoaderError {
+    #[error("Could not read the style: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the style: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("{0} is not registered")]
+    Unregistered(&'static str),
+}
+
+impl<Shader: ParameterizedShader> AssetLoader for ShapeStyleLoader<Shader> {
+    type Asset = ShapeStyle<Shader>;
+    type Settings = ();
+    type Error = ShapeStyleLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        self.read_style(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["style.ron"]
+    }
+}
+
+impl<Shader: ParameterizedShader> ShapeStyleLoader<Shader> {
+    /// Reads a style from the fields of its params
+    fn read_style(&self, bytes: &[u8]) -> Result<ShapeStyle<Shader>, ShapeStyleLoaderError> {
+        let type_registry = self.type_registry.read();
+        let registration = type_registry
+            .get(TypeId::of::<Shader::Params>())
+            .ok_or_else(|| ShapeStyleLoaderError::Unregistered(Shader::Params::type_path()))?;
+
+        let mut deserializer = ron::de::Deserializer::from_bytes(bytes)?;
+        let reflected = TypedReflectDeserializer::new(registration, &type_registry)
+            .deserialize(&mut deserializer)
+            .map_err(|error| deserializer.span_error(error))?;
+
+        // fields missing from the file keep their default values
+        let mut params = Shader::Params::default();
+        params.apply(reflected.as_ref());
+
+        Ok(ShapeStyle::new(params))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::primitives::{RectShader, RectShaderParams};
+
+    #[test]
+    fn styles_are_read_from_the_fields_of_their_params() {
+        let type_registry = TypeRegistryArc::default();
+        type_registry.write().register::<RectShaderParams>();
+        let loader = ShapeStyleLoader::<RectShader> {
+            type_registry,
+            phantom: PhantomData,
+        };
+
+        let style = loader
+            .read_style(b"(color: (red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0), width: 0.5)")
+            .unwrap();
+
+        // fields missing from the style keep their default values
+        assert_eq!(
+            style.params,
+            RectShaderParams {
+                color: LinearRgba::RED,
+                width: 0.5,
+                height: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn shapes_are_drawn_with_the_params_of_their_style() {
+        let mut world = World::new();
+        let mut styles = Assets::<ShapeStyle<RectShader>>::default();
+        let style = styles.add(ShapeStyle::new(RectShaderParams {
+            color: LinearRgba::BLUE,
+            width: 1.0,
+            height: 0.5,
+        }));
+        world.insert_resource(styles);
+
+        let params = world.run_system_once(move |styles: Res<Assets<ShapeStyle<RectShader>>>| {
+            [style.clone(), Handle::default()]
+                .map(|handle| StyledShape::<RectShader>::get_params(&handle, &styles))
+        });
+
+        // shapes whose style has not loaded are drawn with the default params
+        assert_eq!(
+            params,
+            [
+                RectShaderParams {
+                    color: LinearRgba::BLUE,
+                    width: 1.0,
+                    height: 0.5,
+                },
+                RectShaderParams::default(),
+            ]
+        );
+    }
+}
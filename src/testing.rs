@@ -0,0 +1,112 @@
+use std::ops::Range;
+
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    math::FloatOrd,
+    prelude::*,
+    render::{
+        render_phase::{
+            Draw, DrawFunctionId, DrawFunctions, PhaseItemExtraIndex, TrackedRenderPass,
+            ViewSortedRenderPhases,
+        },
+        render_resource::CachedRenderPipelineId,
+    },
+};
+
+use crate::{ShapeBatch, ViewShapeBatches};
+
+struct NoDraw;
+
+impl Draw<Transparent2d> for NoDraw {
+    fn draw<'w>(
+        &mut self,
+        _world: &'w World,
+        _pass: &mut TrackedRenderPass<'w>,
+        _view: Entity,
+        _item: &Transparent2d,
+    ) {
+    }
+}
+
+/// A batch of the shapes in this range of the instance buffer
+pub(crate) fn shape_batch(range: Range<u32>, bounds: Rect) -> ShapeBatch {
+    ShapeBatch {
+        range,
+        order: 0.0,
+        image: None,
+        bounds,
+        culled: None,
+    }
+}
+
+/// The phase items of each view, queued as the shape plugins queue them, for testing the systems which change them.
+/// The entity of each item is its position in its view's phase when it was added
+pub(crate) struct TestPhases {
+    view_batches: ViewShapeBatches,
+    phases: ViewSortedRenderPhases<Transparent2d>,
+    shape_draw_function: DrawFunctionId,
+    other_draw_function: DrawFunctionId,
+}
+
+impl Default for TestPhases {
+    fn default() -> Self {
+        let draw_functions = DrawFunctions::<Transparent2d>::default();
+        let shape_draw_function = draw_functions.write().add(NoDraw);
+        let other_draw_function = draw_functions.write().add(NoDraw);
+
+        let mut view_batches = ViewShapeBatches::default();
+        view_batches.register_draw_function(shape_draw_function);
+
+        Self {
+            view_batches,
+            phases: Default::default(),
+            shape_draw_function,
+            other_draw_function,
+        }
+    }
+}
+
+impl TestPhases {
+    /// Adds a phase item which draws the batch to the view
+    pub fn add_shapes(&mut self, view: Entity, batch: ShapeBatch) {
+        let extra_index = self.view_batches.push(view, batch);
+        self.add(view, self.shape_draw_function, extra_index);
+    }
+
+    /// Adds a phase item which is not drawn by a shape, such as a sprite, to the view
+    pub fn add_other(&mut self, view: Entity) {
+        self.add(view, self.other_draw_function, PhaseItemExtraIndex::NONE);
+    }
+
+    fn add(
+        &mut self,
+        view: Entity,
+        draw_function: DrawFunctionId,
+        extra_index: PhaseItemExtraIndex,
+    ) {
+        let phase = self.phases.entry(view).or_default();
+        phase.add(Transparent2d {
+            draw_function,
+            pipeline: CachedRenderPipelineId::INVALID,
+            entity: Entity::from_raw(phase.items.len() as u32),
+            sort_key: FloatOrd(0.0),
+            batch_range: 0..1,
+            extra_index,
+        });
+    }
+
+    /// Adds the phases and their batches to the world
+    pub fn insert_into(self, world: &mut World) {
+        world.insert_resource(self.view_batches);
+        world.insert_resource(self.phases);
+    }
+}
+
+/// The entities of the items of the view's phase, in the order they are drawn
+pub(crate) fn drawn_items(world: &World, view: Entity) -> Vec<u32> {
+    world.resource::<ViewSortedRenderPhases<Transparent2d>>()[&view]
+        .items
+        .iter()
+        .map(|item| item.entity.index())
+        .collect()
+}
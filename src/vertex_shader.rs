@@ -1,17 +1,15 @@
-use bevy::reflect::Struct;
-
-use crate::parameterized_shader::*;
+use crate::shader_code::ShaderCode;
 
 /// Creates a vertex shader with the correct number of arguments
-pub(crate) fn create_vertex_shader<Shader: ParameterizedShader>() -> bevy::render::render_resource::Shader {
+pub(crate) fn create_vertex_shader(code: &ShaderCode) -> bevy::render::render_resource::Shader {
     // TODO Create this string at compile time?
 
-    let proxy = <Shader::Params as Default>::default();
+    let proxy = code.params.as_ref();
 
     let param_count = proxy.field_len();
 
-    let vertex_params_locations = crate::helpers::format_params_locations::<Shader::Params>(3);
-    let fragment_params_locations = crate::helpers::format_params_locations::<Shader::Params>(1);
+    let fragment_params_locations = crate::helpers::format_params_locations(proxy, 1);
+    let instance_reading = format_instance_reading(code);
 
     let mut params_assignments = "".to_string();
     for index in 0..param_count {
@@ -19,9 +17,13 @@ pub(crate) fn create_vertex_shader<Shader: ParameterizedShader>() -> bevy::rende
         params_assignments.push_str(format!("    out.{name} = vertex.{name};\n").as_str());
     }
 
-    let tp = Shader::type_path();
+    // generic shaders have type paths which are not valid import paths
+    let tp = code.type_path.replace(
+        |c: char| !(c.is_alphanumeric() || c == '_' || c == ':'),
+        "_",
+    );
 
-    let frame_expression: String = Shader::frame_expression().into();
+    let frame_expression = &code.frame_expression;
 
     let source = format!(
         r##"
@@ -34,15 +36,11 @@ struct View {{
 @group(0) @binding(0)
 var<uniform> view: View;
 
-// as specified in `specialize()`
-struct Vertex {{
-@location(0) rotation: vec2<f32>,
-@location(1) position: vec3<f32>,
-@location(2) scale: f32,
-{vertex_params_locations}
-}};
-
+// The instance data, laid out as in `ShapeVertex`
+@group(1) @binding(0)
+var<storage, read> instances: array<u32>;
 
+{instance_reading}
 
 struct VertexOutput {{
 @builtin(position) clip_position: vec4<f32>,
@@ -50,12 +48,14 @@ struct VertexOutput {{
 {fragment_params_locations}
 }};
 
+// as specified in `specialize()`
 @vertex
 fn vertex(
-    vertex: Vertex,
+    @location(0) slot: u32,
     @builtin(vertex_index) i: u32
 ) -> VertexOutput {{
 var out: VertexOutput;
+let vertex = read_instance(slot);
 var frame = {frame_expression};
 
 let x = select(-1., 1., i % 2u == 0u);
@@ -63,8 +63,10 @@ let y = select(-1., 1., (i / 2u) % 2u == 0u);
 let c = vertex.rotation.x;
 let s = vertex.rotation.y;
 
-let rotated = vec2<f32>(x * c - y * s, x * s + y * c);
-let pos = vertex.position + vec3<f32>(rotated * vertex.scale * frame, vertex.position.z);
+// the frame is applied before rotating, so shapes with non-square frames are rotated with their quad
+let corner = vec2<f32>(x, y) * frame;
+let rotated = vec2<f32>(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
+let pos = vertex.position + vec3<f32>(rotated * vertex.scale, vertex.position.z);
 // Project the world position of the mesh into screen position
 out.clip_position = view.view_proj * vec4<f32>(pos, 1.);
 {params_assignments}
@@ -77,3 +79,43 @@ return out;
     let path = file!();
     bevy::render::render_resource::Shader::from_wgsl(source, path)
 }
+
+/// The `Instance` struct, a `read_instance` function which reads it from the `instances` storage buffer,
+/// and the functions used by the frame expression
+pub(crate) fn format_instance_reading(code: &ShaderCode) -> String {
+    let instance_params_fields = crate::helpers::format_struct_fields(code.params.as_ref());
+
+    // rotation, position and scale come before the params
+    const PRE_PARAM_WORDS: u32 = 6;
+    let params_reads = crate::helpers::format_params_reads(
+        code.params.as_ref(),
+        "vertex",
+        "instances",
+        PRE_PARAM_WORDS,
+    );
+    let stride = code.stride;
+    let functions = &code.vertex_functions;
+
+    format!(
+        r##"
+struct Instance {{
+rotation: vec2<f32>,
+position: vec3<f32>,
+scale: f32,
+{instance_params_fields}
+}};
+
+fn read_instance(slot: u32) -> Instance {{
+let base = slot * {stride}u;
+var vertex: Instance;
+vertex.rotation = bitcast<vec2<f32>>(vec2<u32>(instances[base], instances[base + 1u]));
+vertex.position = bitcast<vec3<f32>>(vec3<u32>(instances[base + 2u], instances[base + 3u], instances[base + 4u]));
+vertex.scale = bitcast<f32>(instances[base + 5u]);
+{params_reads}
+return vertex;
+}}
+
+{functions}
+"##
+    )
+}